@@ -7,6 +7,7 @@ use crate::run::Credentials;
 use crate::stores::{sqlite::SQLiteStore, store::Store};
 use crate::utils;
 use anyhow::{anyhow, Result};
+use async_trait::async_trait;
 use cloud_storage::Object;
 use futures::try_join;
 use futures::StreamExt;
@@ -18,6 +19,8 @@ use qdrant_client::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -40,8 +43,35 @@ pub struct TimestampFilter {
     pub lt: Option<u64>,
 }
 
+/// A latitude/longitude pair, stored on points as the `geo` payload field when a document is
+/// upserted with a `location`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// A geospatial filter to apply to the search query, constraining results to chunks whose
+/// document `location` falls within a bounding box or within `radius_meters` of `center`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum GeoFilter {
+    BoundingBox {
+        top_left: GeoPoint,
+        bottom_right: GeoPoint,
+    },
+    Radius {
+        center: GeoPoint,
+        radius_meters: f64,
+    },
+}
+
 /// Filter argument to perform semantic search. It is used to filter the search results based on the
 /// presence of tags or time spans for timestamps.
+///
+/// This is the legacy flat shape, kept for backward compatibility: it is treated as sugar for a
+/// single top-level `FilterExpr::And` (see `SearchFilter::to_expr`). New callers should build a
+/// `FilterExpr` directly to express nested boolean combinations.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SearchFilter {
     pub tags: Option<TagsFilter>,
@@ -53,6 +83,538 @@ impl SearchFilter {
         let filter: SearchFilter = serde_json::from_str(json)?;
         Ok(filter)
     }
+
+    /// Treats the flat `{tags, timestamp}` shape as sugar for a single top-level `And` of its
+    /// non-empty clauses.
+    pub fn to_expr(&self) -> FilterExpr {
+        let mut filters = vec![];
+        if let Some(tags) = &self.tags {
+            filters.push(FilterExpr::Tag(tags.clone()));
+        }
+        if let Some(timestamp) = &self.timestamp {
+            filters.push(FilterExpr::Timestamp(timestamp.clone()));
+        }
+        FilterExpr::And(filters)
+    }
+}
+
+/// A recursively nestable filter expression (`And`/`Or`/`Not` over leaf conditions), lowered to a
+/// Qdrant `Filter` by `to_qdrant_filter`: `And` maps to `must`, `Or` maps to `min_should` with
+/// `min_count = 1` (set explicitly rather than relying on Qdrant's implicit default, so it stays
+/// correct when nested as a sub-condition of another clause), and `Not` maps to `must_not`, with
+/// sub-expressions nested as conditions so expressions like
+/// `(tag A OR tag B) AND NOT (source X) AND timestamp in range` can be expressed directly.
+///
+/// Serializes/deserializes adjacently tagged as `{"op": "...", "args": ...}` so tuple variants
+/// (`And`/`Or`/`Not`) can carry array/boxed payloads alongside the leaf conditions, which reuse
+/// `TagsFilter`/`TimestampFilter`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "op", content = "args", rename_all = "snake_case")]
+pub enum FilterExpr {
+    And(Vec<FilterExpr>),
+    Or(Vec<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Tag(TagsFilter),
+    Timestamp(TimestampFilter),
+    DocumentId(TagsFilter),
+    SourceUrl(TagsFilter),
+    Geo(GeoFilter),
+}
+
+impl FilterExpr {
+    pub fn from_json_str(json: &str) -> Result<Self> {
+        // Try the recursive shape first, falling back to the legacy flat `SearchFilter` shape so
+        // existing callers and stored filter configs keep working.
+        match serde_json::from_str::<FilterExpr>(json) {
+            Ok(expr) => Ok(expr),
+            Err(_) => Ok(SearchFilter::from_json_str(json)?.to_expr()),
+        }
+    }
+
+    /// Parses the small `--filter` query grammar accepted by `cmd_search`: `AND`/`OR`/`NOT` over
+    /// `tag`/`timestamp` clauses, e.g. `tag in [customer-a, customer-b] AND NOT timestamp < 1700000000`.
+    /// Field names are validated against the data source schema (`tag`, `timestamp`) and rejected
+    /// with a clear error otherwise; this is sugar over the same `FilterExpr` the JSON shape
+    /// builds, so it lowers to Qdrant through the same `to_qdrant_filter`.
+    pub fn parse_query(query: &str) -> Result<Self> {
+        FilterQueryParser::new(query).parse()
+    }
+
+    /// Lowers this expression to the top-level Qdrant `Filter` passed to `search_points`/`scroll`.
+    pub fn to_qdrant_filter(&self) -> qdrant::Filter {
+        match self {
+            FilterExpr::And(filters) => qdrant::Filter {
+                must: filters.iter().map(|f| f.to_condition()).collect(),
+                ..Default::default()
+            },
+            FilterExpr::Or(filters) => qdrant::Filter {
+                min_should: Some(qdrant::MinShould {
+                    conditions: filters.iter().map(|f| f.to_condition()).collect(),
+                    min_count: 1,
+                }),
+                ..Default::default()
+            },
+            FilterExpr::Not(filter) => qdrant::Filter {
+                must_not: vec![filter.to_condition()],
+                ..Default::default()
+            },
+            _ => qdrant::Filter {
+                must: vec![self.to_condition()],
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Lowers this expression to a single Qdrant `Condition`, nesting compound expressions
+    /// (`And`/`Or`/`Not`) as a sub-`Filter` condition so they can appear inside another
+    /// `And`/`Or`.
+    fn to_condition(&self) -> qdrant::Condition {
+        match self {
+            FilterExpr::And(_) | FilterExpr::Or(_) | FilterExpr::Not(_) => qdrant::Condition {
+                condition_one_of: Some(qdrant::condition::ConditionOneOf::Filter(
+                    self.to_qdrant_filter(),
+                )),
+            },
+            FilterExpr::Tag(t) => Self::tags_condition("tags", t),
+            FilterExpr::DocumentId(t) => Self::tags_condition("document_id", t),
+            FilterExpr::SourceUrl(t) => Self::tags_condition("source_url", t),
+            FilterExpr::Timestamp(t) => {
+                let mut range = qdrant::Range::default();
+                if let Some(gt) = t.gt {
+                    range.gte = Some(gt as f64);
+                }
+                if let Some(lt) = t.lt {
+                    range.lte = Some(lt as f64);
+                }
+                qdrant::FieldCondition {
+                    key: "timestamp".to_string(),
+                    range: Some(range),
+                    ..Default::default()
+                }
+                .into()
+            }
+            FilterExpr::Geo(geo) => Self::geo_condition(geo),
+        }
+    }
+
+    /// Lowers a bounding-box or center+radius geo leaf to a condition on the `geo` payload field
+    /// (requires documents to have been upserted with a `location`, see `cmd_upsert`).
+    fn geo_condition(geo: &GeoFilter) -> qdrant::Condition {
+        match geo {
+            GeoFilter::BoundingBox {
+                top_left,
+                bottom_right,
+            } => qdrant::FieldCondition {
+                key: "geo".to_string(),
+                geo_bounding_box: Some(qdrant::GeoBoundingBox {
+                    top_left: Some(qdrant::GeoPoint {
+                        lat: top_left.lat,
+                        lon: top_left.lon,
+                    }),
+                    bottom_right: Some(qdrant::GeoPoint {
+                        lat: bottom_right.lat,
+                        lon: bottom_right.lon,
+                    }),
+                }),
+                ..Default::default()
+            }
+            .into(),
+            GeoFilter::Radius {
+                center,
+                radius_meters,
+            } => qdrant::FieldCondition {
+                key: "geo".to_string(),
+                geo_radius: Some(qdrant::GeoRadius {
+                    center: Some(qdrant::GeoPoint {
+                        lat: center.lat,
+                        lon: center.lon,
+                    }),
+                    radius: *radius_meters as f32,
+                }),
+                ..Default::default()
+            }
+            .into(),
+        }
+    }
+
+    /// Lowers an `is_in`/`is_not` leaf over `key` to a single condition. When both are set, the
+    /// leaf nests its own `(must, must_not)` sub-filter so it still composes as one condition.
+    fn tags_condition(key: &str, t: &TagsFilter) -> qdrant::Condition {
+        let is_in_condition = |values: &Vec<String>| -> qdrant::Condition {
+            qdrant::FieldCondition {
+                key: key.to_string(),
+                r#match: Some(qdrant::Match {
+                    match_value: Some(qdrant::r#match::MatchValue::Keywords(
+                        qdrant::RepeatedStrings {
+                            strings: values.clone(),
+                        },
+                    )),
+                }),
+                ..Default::default()
+            }
+            .into()
+        };
+        match (&t.is_in, &t.is_not) {
+            (Some(is_in), None) => is_in_condition(is_in),
+            (None, Some(is_not)) => qdrant::Condition {
+                condition_one_of: Some(qdrant::condition::ConditionOneOf::Filter(qdrant::Filter {
+                    must_not: vec![is_in_condition(is_not)],
+                    ..Default::default()
+                })),
+            },
+            (Some(is_in), Some(is_not)) => qdrant::Condition {
+                condition_one_of: Some(qdrant::condition::ConditionOneOf::Filter(qdrant::Filter {
+                    must: vec![is_in_condition(is_in)],
+                    must_not: vec![is_in_condition(is_not)],
+                    ..Default::default()
+                })),
+            },
+            (None, None) => qdrant::Condition {
+                condition_one_of: Some(qdrant::condition::ConditionOneOf::Filter(
+                    qdrant::Filter::default(),
+                )),
+            },
+        }
+    }
+}
+
+/// Recursive-descent parser for `FilterExpr::parse_query`'s grammar:
+///
+/// ```text
+/// expr   := and (OR and)*
+/// and    := not (AND not)*
+/// not    := NOT not | primary
+/// primary:= '(' expr ')' | clause
+/// clause := 'tag' ('in' | 'not') value_list
+///         | 'timestamp' ('>' | '<') integer
+/// ```
+///
+/// `AND`/`OR`/`NOT`/`in` are matched case-insensitively; bracketed lists (`[a, b]`) and bare
+/// single values (`tag not foo`) are both accepted for tag clauses.
+struct FilterQueryParser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl FilterQueryParser {
+    fn new(query: &str) -> Self {
+        FilterQueryParser {
+            tokens: Self::tokenize(query),
+            pos: 0,
+        }
+    }
+
+    fn tokenize(query: &str) -> Vec<String> {
+        let mut tokens = vec![];
+        let mut current = String::new();
+        for c in query.chars() {
+            match c {
+                '(' | ')' | '[' | ']' | ',' => {
+                    if !current.is_empty() {
+                        tokens.push(std::mem::take(&mut current));
+                    }
+                    tokens.push(c.to_string());
+                }
+                c if c.is_whitespace() => {
+                    if !current.is_empty() {
+                        tokens.push(std::mem::take(&mut current));
+                    }
+                }
+                c => current.push(c),
+            }
+        }
+        if !current.is_empty() {
+            tokens.push(current);
+        }
+        tokens
+    }
+
+    fn parse(mut self) -> Result<FilterExpr> {
+        let expr = self.parse_or()?;
+        if self.pos != self.tokens.len() {
+            return Err(anyhow!(
+                "Unexpected token `{}` in filter expression",
+                self.tokens[self.pos]
+            ));
+        }
+        Ok(expr)
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr> {
+        let mut exprs = vec![self.parse_and()?];
+        while self.eat_keyword("or") {
+            exprs.push(self.parse_and()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            FilterExpr::Or(exprs)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr> {
+        let mut exprs = vec![self.parse_not()?];
+        while self.eat_keyword("and") {
+            exprs.push(self.parse_not()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            FilterExpr::And(exprs)
+        })
+    }
+
+    fn parse_not(&mut self) -> Result<FilterExpr> {
+        if self.eat_keyword("not") {
+            return Ok(FilterExpr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<FilterExpr> {
+        if self.eat_token("(") {
+            let expr = self.parse_or()?;
+            self.expect_token(")")?;
+            return Ok(expr);
+        }
+        self.parse_clause()
+    }
+
+    fn parse_clause(&mut self) -> Result<FilterExpr> {
+        let field = self.next_token()?.to_lowercase();
+        match field.as_str() {
+            "tag" | "tags" => {
+                let op = self.next_token()?.to_lowercase();
+                match op.as_str() {
+                    "in" => Ok(FilterExpr::Tag(TagsFilter {
+                        is_in: Some(self.parse_value_list()?),
+                        is_not: None,
+                    })),
+                    "not" => Ok(FilterExpr::Tag(TagsFilter {
+                        is_in: None,
+                        is_not: Some(self.parse_value_list()?),
+                    })),
+                    other => Err(anyhow!(
+                        "Unknown operator `{}` for field `tag`, expected `in` or `not`",
+                        other
+                    )),
+                }
+            }
+            "timestamp" => {
+                let op = self.next_token()?;
+                let value: u64 = self
+                    .next_token()?
+                    .parse()
+                    .map_err(|_| anyhow!("Expected an integer timestamp value"))?;
+                match op.as_str() {
+                    ">" => Ok(FilterExpr::Timestamp(TimestampFilter {
+                        gt: Some(value),
+                        lt: None,
+                    })),
+                    "<" => Ok(FilterExpr::Timestamp(TimestampFilter {
+                        gt: None,
+                        lt: Some(value),
+                    })),
+                    other => Err(anyhow!(
+                        "Unknown operator `{}` for field `timestamp`, expected `>` or `<`",
+                        other
+                    )),
+                }
+            }
+            other => Err(anyhow!(
+                "Unknown field `{}` in filter expression, expected `tag` or `timestamp`",
+                other
+            )),
+        }
+    }
+
+    /// Accepts either a bracketed list (`[a, b]`) or a single bare value (`foo`).
+    fn parse_value_list(&mut self) -> Result<Vec<String>> {
+        if !self.eat_token("[") {
+            return Ok(vec![self.next_token()?]);
+        }
+        let mut values = vec![];
+        if !self.peek_token("]") {
+            loop {
+                values.push(self.next_token()?);
+                if !self.eat_token(",") {
+                    break;
+                }
+            }
+        }
+        self.expect_token("]")?;
+        Ok(values)
+    }
+
+    fn next_token(&mut self) -> Result<String> {
+        let t = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unexpected end of filter expression"))?;
+        self.pos += 1;
+        Ok(t)
+    }
+
+    fn peek_token(&self, s: &str) -> bool {
+        self.tokens.get(self.pos).map(|t| t == s).unwrap_or(false)
+    }
+
+    fn eat_token(&mut self, s: &str) -> bool {
+        if self.peek_token(s) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, kw: &str) -> bool {
+        if self
+            .tokens
+            .get(self.pos)
+            .map(|t| t.eq_ignore_ascii_case(kw))
+            .unwrap_or(false)
+        {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_token(&mut self, s: &str) -> Result<()> {
+        if self.eat_token(s) {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Expected `{}` in filter expression, found `{}`",
+                s,
+                self.tokens
+                    .get(self.pos)
+                    .cloned()
+                    .unwrap_or_else(|| "<eof>".to_string())
+            ))
+        }
+    }
+}
+
+/// Why a chunk is present in a search result: either it was itself matched by the query, or (for
+/// a future expansion strategy that returns standalone neighbor chunks rather than splicing their
+/// text into the match) it was pulled in purely as surrounding context. `target_document_tokens`
+/// expansion today only splices neighbor text into the matched chunk it belongs to, so it never
+/// produces a chunk with reason `Expansion`; see `ChunkScoreDetails::context_expanded` for that
+/// case.
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkScoreReason {
+    #[default]
+    Match,
+    Expansion,
+}
+
+/// A breakdown of how a chunk's `score` was computed, since once filtering, hybrid fusion, or
+/// `target_document_tokens` expansion are involved a single opaque `score` is not enough to
+/// explain why a chunk ranked where it did.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ChunkScoreDetails {
+    /// Raw cosine similarity from the dense vector search, if the chunk was returned by it.
+    pub semantic_score: Option<f64>,
+    /// Raw keyword match score from the full-text search, if the chunk was returned by it
+    /// (hybrid search only).
+    pub keyword_score: Option<f64>,
+    /// `semantic_score` min-max normalized against the candidate set (`SearchMode::Hybrid` only).
+    pub semantic_norm: Option<f64>,
+    /// `keyword_score` min-max normalized against the candidate set (`SearchMode::Hybrid` only).
+    pub keyword_norm: Option<f64>,
+    /// The fused (RRF) or combined (`semantic_ratio`) score actually used to rank/sort, i.e. what
+    /// ends up in `Chunk.score` for hybrid search.
+    pub combined_score: Option<f64>,
+    /// 1-based rank of the chunk in the dense vector search's ordering, if present there.
+    pub semantic_rank: Option<usize>,
+    /// 1-based rank of the chunk in the keyword search's ordering, if present there.
+    pub keyword_rank: Option<usize>,
+    pub reason: ChunkScoreReason,
+    /// Set when `target_document_tokens` expansion spliced neighboring chunk text into this
+    /// chunk's `text`. Unlike `reason`, this doesn't change whether the chunk itself matched the
+    /// query — it still did — it just flags that its `text` now includes free surrounding context.
+    #[serde(default)]
+    pub context_expanded: bool,
+}
+
+/// Per-stage wall-clock timings for a single `search` call, reported by `cmd_bench` so regressions
+/// in a specific stage (e.g. the expansion scroll after a chunk size change) don't hide behind an
+/// end-to-end latency number.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SearchTiming {
+    pub qdrant_ms: u128,
+    pub keyword_ms: u128,
+    pub blob_fetch_ms: u128,
+    pub expansion_ms: u128,
+    pub total_ms: u128,
+}
+
+/// A single search request replayed by `cmd_bench`, as specified in a workload JSON file (a JSON
+/// array of these objects).
+#[derive(Debug, Deserialize, Clone)]
+pub struct BenchRequest {
+    pub query: String,
+    #[serde(default = "BenchRequest::default_top_k")]
+    pub top_k: usize,
+    #[serde(default)]
+    pub full_text: bool,
+    pub target_document_tokens: Option<usize>,
+    pub filter: Option<FilterExpr>,
+}
+
+impl BenchRequest {
+    fn default_top_k() -> usize {
+        8
+    }
+}
+
+/// p50/p90/p99 of a latency sample, in milliseconds.
+#[derive(Debug, Serialize, Default)]
+pub struct BenchPercentiles {
+    pub p50: u128,
+    pub p90: u128,
+    pub p99: u128,
+}
+
+impl BenchPercentiles {
+    fn from_samples(mut samples: Vec<u128>) -> Self {
+        if samples.is_empty() {
+            return BenchPercentiles::default();
+        }
+        samples.sort();
+        let at = |p: f64| samples[(((samples.len() - 1) as f64) * p).round() as usize];
+        BenchPercentiles {
+            p50: at(0.50),
+            p90: at(0.90),
+            p99: at(0.99),
+        }
+    }
+}
+
+/// Machine-readable report emitted by `cmd_bench`, meant to be diffed run-to-run (e.g. against a
+/// baseline commit) to catch per-stage latency regressions.
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+    pub request_count: usize,
+    pub error_count: usize,
+    pub total_ms: BenchPercentiles,
+    pub qdrant_ms: BenchPercentiles,
+    pub keyword_ms: BenchPercentiles,
+    pub blob_fetch_ms: BenchPercentiles,
+    pub expansion_ms: BenchPercentiles,
+}
+
+/// A lightweight pointer recorded on a deduplicated chunk's canonical point, identifying another
+/// document that contains the same chunk content at `offset`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChunkRef {
+    pub document_id: String,
+    pub offset: usize,
 }
 
 /// A Chunk is a subset of a document that was inserted into vector search db. `hash` covers both
@@ -65,6 +627,17 @@ pub struct Chunk {
     pub offset: usize,
     pub vector: Option<Vec<f64>>,
     pub score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score_details: Option<ChunkScoreDetails>,
+    /// Content hash of the embedder input (`blake3` of the raw chunk text, or of its rendered
+    /// `embedding_template` when one is configured, which can include document fields) used to
+    /// dedup identical chunks across documents. Distinct from `hash`, which stays document-scoped so it
+    /// keeps working as the per-search merge key for RRF/hybrid fusion.
+    pub content_hash: String,
+    /// Other documents that share this exact chunk content, populated on search results so a
+    /// single deduplicated point can still surface in every document that contains it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dedup_refs: Vec<ChunkRef>,
 }
 
 /// Document is used as a data-strucutre for insertion into the SQL store (no chunks, they are
@@ -86,6 +659,13 @@ pub struct Document {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
     pub token_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeoPoint>,
+    /// Structured key/value metadata (e.g. `author`, `lang`, `doc_type`), distinct from freeform
+    /// `tags`: each pair is indexed for exact-match lookup, see `--meta`/`--where` on
+    /// `cmd_upsert`/`cmd_list`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
 }
 
 impl Document {
@@ -97,6 +677,8 @@ impl Document {
         source_url: &Option<String>,
         hash: &str,
         text_size: u64,
+        location: &Option<GeoPoint>,
+        metadata: &HashMap<String, String>,
     ) -> Result<Self> {
         Ok(Document {
             data_source_id: data_source_id.to_string(),
@@ -111,6 +693,8 @@ impl Document {
             chunks: vec![],
             text: None,
             token_count: None,
+            location: location.clone(),
+            metadata: metadata.clone(),
         })
     }
 }
@@ -121,6 +705,64 @@ pub struct DocumentVersion {
     pub hash: String,
 }
 
+/// Vector quantization applied to a collection at `setup()` time to cut resident memory for
+/// large, high-dimensional data sources. The compressed codes are used for the initial ANN scan;
+/// `search` oversamples and rescores against the original on-disk f32 vectors to preserve recall.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QuantizationConfig {
+    /// Scalar int8 quantization. `quantile` (e.g. `0.99`) trims outlier values before quantizing
+    /// to improve precision; `always_ram` keeps the quantized codes in RAM even when the
+    /// collection's full vectors are memory-mapped to disk.
+    Scalar { quantile: f32, always_ram: bool },
+    /// Binary quantization, for very high-dimensional embeddings where even int8 codes are too
+    /// large to keep fully in RAM.
+    Binary { always_ram: bool },
+}
+
+impl QuantizationConfig {
+    fn to_qdrant(&self) -> qdrant::QuantizationConfig {
+        match self {
+            QuantizationConfig::Scalar {
+                quantile,
+                always_ram,
+            } => qdrant::QuantizationConfig {
+                quantization: Some(qdrant::quantization_config::Quantization::Scalar(
+                    qdrant::ScalarQuantization {
+                        r#type: qdrant::QuantizationType::Int8.into(),
+                        quantile: Some(*quantile),
+                        always_ram: Some(*always_ram),
+                    },
+                )),
+            },
+            QuantizationConfig::Binary { always_ram } => qdrant::QuantizationConfig {
+                quantization: Some(qdrant::quantization_config::Quantization::Binary(
+                    qdrant::BinaryQuantization {
+                        always_ram: Some(*always_ram),
+                    },
+                )),
+            },
+        }
+    }
+}
+
+/// Explicit choice of how `search` combines the dense vector ranking with the keyword ranking
+/// when `full_text=true`. Supersedes the ad-hoc `semantic_ratio` config field, which is kept as a
+/// shorthand for `Hybrid`.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SearchMode {
+    /// Ignore the keyword ranking: chunks are ranked as with `full_text=false`. The keyword search
+    /// still runs and its scores and ranks still appear in `score_details`.
+    SemanticOnly,
+    /// Min-max normalize both rankings' scores and blend them: `alpha` weights the semantic
+    /// score, `1.0 - alpha` weights the keyword score.
+    Hybrid { alpha: f64 },
+    /// Fuse both rankings by Reciprocal Rank Fusion with the given constant `k` (the default,
+    /// `RRF_K`, is used when `search_mode` and `semantic_ratio` are both unset).
+    Rrf { k: f64 },
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct DataSourceConfig {
     pub provider_id: ProviderID,
@@ -129,6 +771,54 @@ pub struct DataSourceConfig {
     pub splitter_id: SplitterID,
     pub max_chunk_size: usize,
     pub use_cache: bool,
+    // When set, hybrid search (`full_text=true`) combines dense and keyword scores by min-max
+    // normalizing each within the candidate set and weighting them by this ratio, instead of
+    // fusing their ranks with RRF. `1.0` is pure semantic ranking, `0.0` is pure keyword ranking.
+    //
+    // Superseded by `search_mode`, kept as a shorthand for `SearchMode::Hybrid`: if both are
+    // unset, hybrid search falls back to `SearchMode::Rrf` with the default `RRF_K`.
+    #[serde(default)]
+    pub semantic_ratio: Option<f64>,
+    // Explicit choice of how hybrid search (`full_text=true`) combines the dense and keyword
+    // rankings. Takes precedence over `semantic_ratio` when set.
+    #[serde(default)]
+    pub search_mode: Option<SearchMode>,
+    // When set, `setup()` creates the collection with this quantization applied, and `search`
+    // oversamples/rescores against it to preserve recall against the compressed codes.
+    #[serde(default)]
+    pub quantization: Option<QuantizationConfig>,
+    // Oversampling factor applied to `top_k` when searching a quantized collection, i.e. how many
+    // more candidates to fetch using the compressed vectors before rescoring against the original
+    // f32 vectors. Defaults to `2.0` when `quantization` is set and this is unspecified.
+    #[serde(default)]
+    pub quantization_oversampling: Option<f64>,
+    // Whether to rescore the oversampled candidates against the original f32 vectors. Defaults to
+    // `true` when `quantization` is set and this is unspecified.
+    #[serde(default)]
+    pub quantization_rescore: Option<bool>,
+    // When set, each chunk is rendered through this template before being embedded, so document
+    // metadata (tags, source, id) can influence the vector without changing the stored chunk
+    // text. Supports the placeholders `{document_id}`, `{source_url}`, `{tags}`, and `{chunk}`.
+    #[serde(default)]
+    pub embedding_template: Option<String>,
+}
+
+/// Renders `template` for a chunk about to be embedded, substituting `{document_id}`,
+/// `{source_url}`, `{tags}`, and `{chunk}`. Only the rendered text is embedded; the stored
+/// `Chunk.text` and `text` payload remain the raw chunk so display and keyword search are
+/// unaffected.
+fn render_embedding_template(
+    template: &str,
+    document_id: &str,
+    source_url: &Option<String>,
+    tags: &[String],
+    chunk: &str,
+) -> String {
+    template
+        .replace("{document_id}", document_id)
+        .replace("{source_url}", source_url.as_deref().unwrap_or(""))
+        .replace("{tags}", &tags.join(", "))
+        .replace("{chunk}", chunk)
 }
 
 /// The `data_source_id` is the unique identifier that allows routing to the right data in SQL store
@@ -193,92 +883,830 @@ fn target_document_tokens_offsets(
     results
 }
 
-impl DataSource {
-    pub fn new(project: &Project, data_source_id: &str, config: &DataSourceConfig) -> Self {
-        DataSource {
-            project: project.clone(),
-            created: utils::now(),
-            data_source_id: data_source_id.to_string(),
-            internal_id: utils::new_id(),
-            config: config.clone(),
+/// Default Reciprocal Rank Fusion constant. Larger values flatten the contribution of rank
+/// position, smaller values make the top ranks dominate more strongly.
+const RRF_K: f64 = 60.0;
+
+/// Fuses several ranked lists of keys (best first) into a single score per key using Reciprocal
+/// Rank Fusion: `score(key) = Σ 1/(k + rank)` over every list the key appears in, where `rank` is
+/// the 1-based position of the key in that list. This avoids having to normalize incompatible
+/// score scales (e.g. cosine similarity vs. a keyword match score) across rankers.
+fn reciprocal_rank_fusion(rankings: &[Vec<String>], k: f64) -> HashMap<String, f64> {
+    let mut scores: HashMap<String, f64> = HashMap::new();
+    for ranking in rankings {
+        for (i, key) in ranking.iter().enumerate() {
+            let rank = (i + 1) as f64;
+            *scores.entry(key.clone()).or_insert(0.0) += 1.0 / (k + rank);
         }
     }
+    scores
+}
 
-    pub fn new_from_store(
-        project: &Project,
-        created: u64,
-        data_source_id: &str,
-        internal_id: &str,
-        config: &DataSourceConfig,
-    ) -> Self {
-        DataSource {
-            project: project.clone(),
-            created,
-            data_source_id: data_source_id.to_string(),
-            internal_id: internal_id.to_string(),
-            config: config.clone(),
-        }
-    }
+/// Parses a `(document_id, Chunk)` pair out of a Qdrant point payload. Shared by the dense vector
+/// search path and the keyword search path, which both scroll/search the same collection and
+/// payload shape but assign `score` differently.
+fn parse_chunk_result(
+    payload: &HashMap<String, qdrant::Value>,
+    score: Option<f64>,
+) -> Result<(String, Chunk)> {
+    let document_id = match payload.get("document_id") {
+        Some(t) => match t.kind {
+            Some(qdrant::value::Kind::StringValue(ref s)) => s.clone(),
+            _ => Err(anyhow!("Missing `document_id` in chunk payload"))?,
+        },
+        None => Err(anyhow!("Missing `document_id` in chunk payload"))?,
+    };
+    let text = match payload.get("text") {
+        Some(t) => match t.kind {
+            Some(qdrant::value::Kind::StringValue(ref s)) => s.clone(),
+            _ => Err(anyhow!("Missing `text` in chunk payload"))?,
+        },
+        None => Err(anyhow!("Missing `text` in chunk payload"))?,
+    };
+    let chunk_hash = match payload.get("chunk_hash") {
+        Some(t) => match t.kind {
+            Some(qdrant::value::Kind::StringValue(ref s)) => s.clone(),
+            _ => Err(anyhow!("Missing `chunk_hash` in chunk payload"))?,
+        },
+        None => Err(anyhow!("Missing `chunk_hash` in chunk payload"))?,
+    };
+    let chunk_offset = match payload.get("chunk_offset") {
+        Some(t) => match t.kind {
+            Some(qdrant::value::Kind::IntegerValue(i)) => i,
+            _ => Err(anyhow!("Missing `chunk_offset` in chunk payload"))?,
+        },
+        None => Err(anyhow!("Missing `chunk_offset` in chunk payload"))?,
+    };
+    // Older points created before dedup shipped won't carry `content_hash`; fall back to the
+    // document-scoped `hash` so they still round-trip.
+    let content_hash = match payload.get("content_hash") {
+        Some(t) => match t.kind {
+            Some(qdrant::value::Kind::StringValue(ref s)) => s.clone(),
+            _ => chunk_hash.clone(),
+        },
+        None => chunk_hash.clone(),
+    };
+    // `chunk_refs` is a JSON-encoded array of `ChunkRef`, set only on canonical points that other
+    // documents' identical chunks have been deduplicated against.
+    let dedup_refs = match payload.get("chunk_refs") {
+        Some(t) => match t.kind {
+            Some(qdrant::value::Kind::StringValue(ref s)) => {
+                serde_json::from_str::<Vec<ChunkRef>>(s).unwrap_or_default()
+            }
+            _ => vec![],
+        },
+        None => vec![],
+    };
+    Ok((
+        document_id,
+        Chunk {
+            text,
+            hash: chunk_hash,
+            offset: chunk_offset as usize,
+            vector: None,
+            score,
+            score_details: None,
+            content_hash,
+            dedup_refs,
+        },
+    ))
+}
 
-    pub fn created(&self) -> u64 {
-        self.created
+/// Whether a candidate canonical point's filterable payload (`tags`/`timestamp`/`source_url`/
+/// `geo`) matches a document's own metadata. A single Qdrant point can only carry one filterable
+/// payload, so a chunk is only safe to dedup against an existing canonical point when every field
+/// a tag/timestamp/geo search filter runs against is identical — otherwise the shared point would
+/// silently fail to match a filtered search for whichever document's metadata it doesn't carry.
+fn payload_matches_filter_metadata(
+    payload: &HashMap<String, qdrant::Value>,
+    tags: &[String],
+    timestamp: u64,
+    source_url: &Option<String>,
+    location: &Option<GeoPoint>,
+) -> bool {
+    let payload_tags = match payload.get("tags") {
+        Some(t) => match &t.kind {
+            Some(qdrant::value::Kind::ListValue(l)) => l
+                .values
+                .iter()
+                .filter_map(|v| match &v.kind {
+                    Some(qdrant::value::Kind::StringValue(s)) => Some(s.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            _ => vec![],
+        },
+        None => vec![],
+    };
+    let mut sorted_payload_tags = payload_tags;
+    sorted_payload_tags.sort();
+    let mut sorted_tags = tags.to_vec();
+    sorted_tags.sort();
+    if sorted_payload_tags != sorted_tags {
+        return false;
     }
 
-    pub fn data_source_id(&self) -> &str {
-        &self.data_source_id
+    let payload_timestamp = match payload.get("timestamp") {
+        Some(t) => match t.kind {
+            Some(qdrant::value::Kind::IntegerValue(i)) => i as u64,
+            _ => return false,
+        },
+        None => return false,
+    };
+    if payload_timestamp != timestamp {
+        return false;
     }
 
-    pub fn internal_id(&self) -> &str {
-        &self.internal_id
+    let payload_source_url = match payload.get("source_url") {
+        Some(t) => match &t.kind {
+            Some(qdrant::value::Kind::StringValue(s)) if !s.is_empty() => Some(s.clone()),
+            _ => None,
+        },
+        None => None,
+    };
+    if payload_source_url != *source_url {
+        return false;
     }
 
-    pub fn config(&self) -> &DataSourceConfig {
-        &self.config
+    let payload_geo = match payload.get("geo") {
+        Some(t) => match &t.kind {
+            Some(qdrant::value::Kind::StructValue(s)) => {
+                let lat = s.fields.get("lat").and_then(|v| match v.kind {
+                    Some(qdrant::value::Kind::DoubleValue(d)) => Some(d),
+                    _ => None,
+                });
+                let lon = s.fields.get("lon").and_then(|v| match v.kind {
+                    Some(qdrant::value::Kind::DoubleValue(d)) => Some(d),
+                    _ => None,
+                });
+                lat.zip(lon).map(|(lat, lon)| GeoPoint { lat, lon })
+            }
+            _ => None,
+        },
+        None => None,
+    };
+    match (&payload_geo, location) {
+        (Some(a), Some(b)) => a.lat == b.lat && a.lon == b.lon,
+        (None, None) => true,
+        _ => false,
     }
+}
 
-    fn qdrant_collection(&self) -> String {
-        format!("ds_{}", self.internal_id)
+fn min_max(values: &[f64]) -> (f64, f64) {
+    (
+        values.iter().cloned().fold(f64::INFINITY, f64::min),
+        values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+    )
+}
+
+fn normalize(value: f64, min: f64, max: f64) -> f64 {
+    if (max - min).abs() < f64::EPSILON {
+        0.0
+    } else {
+        (value - min) / (max - min)
     }
+}
 
-    async fn qdrant_client(&self) -> Result<QdrantClient> {
-        match std::env::var("QDRANT_URL") {
-            Ok(url) => {
-                let mut config = QdrantClientConfig::from_url(&url);
-                match std::env::var("QDRANT_API_KEY") {
-                    Ok(api_key) => {
-                        config.set_api_key(&api_key);
-                        QdrantClient::new(Some(config))
-                    }
-                    Err(_) => Err(anyhow!("QDRANT_API_KEY is not set"))?,
-                }
-            }
-            Err(_) => Err(anyhow!("QDRANT_URL is not set"))?,
-        }
+/// Alternative to `reciprocal_rank_fusion`: min-max normalizes the dense cosine scores and the
+/// keyword scores into `[0, 1]` within the candidate set, then ranks by
+/// `combined = semantic_ratio * semantic_norm + (1 - semantic_ratio) * keyword_norm`.
+/// `semantic_ratio = 1.0` reproduces pure vector ranking, `0.0` reproduces pure keyword ranking.
+fn combine_weighted(
+    dense_chunks: Vec<(String, Chunk)>,
+    keyword_chunks: Vec<(String, Chunk)>,
+    semantic_ratio: f64,
+    top_k: usize,
+    score_details_by_hash: &mut HashMap<String, ChunkScoreDetails>,
+) -> Vec<(String, Chunk)> {
+    let (dense_min, dense_max) = min_max(
+        &dense_chunks
+            .iter()
+            .map(|(_, c)| c.score.unwrap_or(0.0))
+            .collect::<Vec<_>>(),
+    );
+    let (keyword_min, keyword_max) = min_max(
+        &keyword_chunks
+            .iter()
+            .map(|(_, c)| c.score.unwrap_or(0.0))
+            .collect::<Vec<_>>(),
+    );
+
+    // hash -> (document_id, semantic_norm, keyword_norm)
+    let mut components: HashMap<String, (String, f64, f64)> = HashMap::new();
+    for (document_id, chunk) in dense_chunks.iter() {
+        let norm = normalize(chunk.score.unwrap_or(0.0), dense_min, dense_max);
+        components.insert(chunk.hash.clone(), (document_id.clone(), norm, 0.0));
+    }
+    for (document_id, chunk) in keyword_chunks.iter() {
+        let norm = normalize(chunk.score.unwrap_or(0.0), keyword_min, keyword_max);
+        components
+            .entry(chunk.hash.clone())
+            .and_modify(|(_, _, keyword_norm)| *keyword_norm = norm)
+            .or_insert((document_id.clone(), 0.0, norm));
     }
 
-    pub async fn setup(&self, credentials: Credentials) -> Result<()> {
-        let mut embedder = provider(self.config.provider_id).embedder(self.config.model_id.clone());
-        embedder.initialize(credentials).await?;
+    let chunks_by_hash: HashMap<String, Chunk> = dense_chunks
+        .into_iter()
+        .chain(keyword_chunks.into_iter())
+        .map(|(_, c)| (c.hash.clone(), c))
+        .collect();
 
-        // GCP store created data to test GCP.
-        let bucket = match std::env::var("DUST_DATA_SOURCES_BUCKET") {
-            Ok(bucket) => bucket,
-            Err(_) => Err(anyhow!("DUST_DATA_SOURCES_BUCKET is not set"))?,
-        };
+    let mut combined = components
+        .into_iter()
+        .filter_map(|(hash, (document_id, semantic_norm, keyword_norm))| {
+            let mut chunk = chunks_by_hash.get(&hash)?.clone();
+            chunk.score =
+                Some(semantic_ratio * semantic_norm + (1.0 - semantic_ratio) * keyword_norm);
+            let details = score_details_by_hash.entry(hash).or_default();
+            details.semantic_norm = Some(semantic_norm);
+            details.keyword_norm = Some(keyword_norm);
+            Some((document_id, chunk))
+        })
+        .collect::<Vec<_>>();
 
-        let bucket_path = format!("{}/{}", self.project.project_id(), self.internal_id);
-        let data_source_created_path = format!("{}/created.txt", bucket_path);
+    combined.sort_by(|a, b| {
+        b.1.score
+            .unwrap_or(0.0)
+            .partial_cmp(&a.1.score.unwrap_or(0.0))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    combined.truncate(top_k);
+    combined
+}
 
-        Object::create(
-            &bucket,
-            format!("{}", self.created).as_bytes().to_vec(),
-            &data_source_created_path,
-            "application/text",
-        )
-        .await?;
+/// Naive keyword relevance: the number of whitespace-delimited query terms that occur verbatim
+/// (case-insensitive) in `text`. Used only to order candidates returned by the Qdrant full-text
+/// match filter before they are fed into Reciprocal Rank Fusion, since that filter itself returns
+/// matches unordered.
+fn keyword_term_score(text: &str, query_terms: &[String]) -> f64 {
+    let lower = text.to_lowercase();
+    query_terms
+        .iter()
+        .filter(|term| lower.contains(term.as_str()))
+        .count() as f64
+}
 
-        utils::done(&format!(
-            "Created GCP bucket for data_source `{}`",
+/// A criterion in the ordered list passed to `search`'s `rank_by`, evaluated lexicographically:
+/// the first criterion that distinguishes two candidates wins, ties fall through to the next.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RankCriterion {
+    /// The existing cosine/fused score, descending.
+    Similarity,
+    /// The parent document's `timestamp`, descending (freshest first).
+    Recency,
+    /// Fraction of the query's whitespace-delimited terms occurring verbatim in the chunk text,
+    /// descending.
+    Exactness,
+    /// Minimum token span in the chunk that contains the most query terms, ascending.
+    Proximity,
+}
+
+impl RankCriterion {
+    /// Parses the comma-separated list accepted by `cmd_search --rank-by`, e.g.
+    /// `similarity,exactness,recency`.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>> {
+        s.split(',')
+            .map(|c| c.trim())
+            .filter(|c| !c.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "similarity" => Ok(RankCriterion::Similarity),
+            "recency" => Ok(RankCriterion::Recency),
+            "exactness" => Ok(RankCriterion::Exactness),
+            "proximity" => Ok(RankCriterion::Proximity),
+            other => Err(anyhow!(
+                "Unknown rank criterion `{}`, expected one of `similarity`, `recency`, \
+                 `exactness`, `proximity`",
+                other
+            )),
+        }
+    }
+}
+
+/// A chunk's evaluated value for each `RankCriterion`, computed once per candidate so the
+/// comparator doesn't recompute exactness/proximity on every pairwise comparison during sort.
+struct RankKey {
+    similarity: f64,
+    recency: u64,
+    exactness: f64,
+    proximity: usize,
+}
+
+fn rank_key(chunk: &Chunk, document_timestamp: u64, query_terms: &[String]) -> RankKey {
+    RankKey {
+        similarity: chunk.score.unwrap_or(0.0),
+        recency: document_timestamp,
+        exactness: if query_terms.is_empty() {
+            0.0
+        } else {
+            keyword_term_score(&chunk.text, query_terms) / query_terms.len() as f64
+        },
+        proximity: proximity_span(&chunk.text, query_terms),
+    }
+}
+
+/// Lexicographic comparator over `criteria`: the first criterion that distinguishes `a` and `b`
+/// decides the ordering, later criteria only break ties.
+fn rank_key_cmp(a: &RankKey, b: &RankKey, criteria: &[RankCriterion]) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    for criterion in criteria {
+        let ord = match criterion {
+            RankCriterion::Similarity => {
+                b.similarity.partial_cmp(&a.similarity).unwrap_or(Ordering::Equal)
+            }
+            RankCriterion::Recency => b.recency.cmp(&a.recency),
+            RankCriterion::Exactness => {
+                b.exactness.partial_cmp(&a.exactness).unwrap_or(Ordering::Equal)
+            }
+            RankCriterion::Proximity => a.proximity.cmp(&b.proximity),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+/// The minimum token span in `text` that contains every distinct query term that occurs in it at
+/// all (i.e. the most query terms reachable), via the classic minimum-window-with-K-distinct
+/// sliding window over the matched token positions. `usize::MAX` if no query term occurs.
+fn proximity_span(text: &str, query_terms: &[String]) -> usize {
+    if query_terms.is_empty() {
+        return usize::MAX;
+    }
+    let term_set = query_terms.iter().collect::<std::collections::HashSet<_>>();
+    let tokens = text.split_whitespace().map(|t| t.to_lowercase()).collect::<Vec<_>>();
+    let matches = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| term_set.contains(t))
+        .collect::<Vec<_>>();
+    if matches.is_empty() {
+        return usize::MAX;
+    }
+
+    let target_distinct = matches
+        .iter()
+        .map(|(_, t)| t.as_str())
+        .collect::<std::collections::HashSet<_>>()
+        .len();
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    let mut distinct = 0;
+    let mut left = 0;
+    let mut best_span = usize::MAX;
+    for right in 0..matches.len() {
+        let (_, term) = &matches[right];
+        let count = counts.entry(term.as_str()).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            distinct += 1;
+        }
+        while distinct == target_distinct {
+            let span = matches[right].0 - matches[left].0 + 1;
+            if span < best_span {
+                best_span = span;
+            }
+            let (_, left_term) = &matches[left];
+            let left_count = counts.get_mut(left_term.as_str()).unwrap();
+            *left_count -= 1;
+            if *left_count == 0 {
+                distinct -= 1;
+            }
+            left += 1;
+        }
+    }
+
+    best_span
+}
+
+/// Backing object storage for document blobs (raw text, tags, timestamps). Abstracted behind a
+/// trait so that self-hosters are not forced onto GCP; the backend is selected at call time via
+/// `DUST_DATA_SOURCES_BLOB_STORE`.
+#[async_trait]
+pub trait DocumentBlobStore: Send + Sync {
+    async fn download(&self, path: &str) -> Result<Vec<u8>>;
+    async fn upload(&self, path: &str, bytes: Vec<u8>, content_type: &str) -> Result<()>;
+    async fn delete(&self, prefix: &str) -> Result<()>;
+}
+
+pub struct GcsBlobStore {
+    bucket: String,
+}
+
+impl GcsBlobStore {
+    pub fn new(bucket: String) -> Self {
+        GcsBlobStore { bucket }
+    }
+}
+
+#[async_trait]
+impl DocumentBlobStore for GcsBlobStore {
+    async fn download(&self, path: &str) -> Result<Vec<u8>> {
+        Ok(Object::download(&self.bucket, path).await?)
+    }
+
+    async fn upload(&self, path: &str, bytes: Vec<u8>, content_type: &str) -> Result<()> {
+        Object::create(&self.bucket, bytes, path, content_type).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, prefix: &str) -> Result<()> {
+        let objects = Object::list_prefix(&self.bucket, prefix).await?;
+        for page in objects {
+            for object in page.items {
+                Object::delete(&self.bucket, &object.name).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// S3-compatible object storage (AWS S3, MinIO, Garage, ...), configured via
+/// `DUST_DATA_SOURCES_S3_ENDPOINT` / `DUST_DATA_SOURCES_S3_REGION` for on-prem deployments.
+pub struct S3BlobStore {
+    bucket: String,
+    client: aws_sdk_s3::Client,
+}
+
+impl S3BlobStore {
+    pub async fn from_env(bucket: String) -> Result<Self> {
+        let region = std::env::var("DUST_DATA_SOURCES_S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .region(aws_sdk_s3::config::Region::new(region));
+        if let Ok(endpoint) = std::env::var("DUST_DATA_SOURCES_S3_ENDPOINT") {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let sdk_config = loader.load().await;
+        let s3_config = aws_sdk_s3::config::Builder::from(&sdk_config)
+            // MinIO and Garage expect path-style bucket addressing rather than virtual-hosted.
+            .force_path_style(true)
+            .build();
+
+        Ok(S3BlobStore {
+            bucket,
+            client: aws_sdk_s3::Client::from_conf(s3_config),
+        })
+    }
+}
+
+#[async_trait]
+impl DocumentBlobStore for S3BlobStore {
+    async fn download(&self, path: &str) -> Result<Vec<u8>> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(path)
+            .send()
+            .await?;
+        let bytes = object.body.collect().await?;
+        Ok(bytes.into_bytes().to_vec())
+    }
+
+    async fn upload(&self, path: &str, bytes: Vec<u8>, content_type: &str) -> Result<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(path)
+            .body(bytes.into())
+            .content_type(content_type)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, prefix: &str) -> Result<()> {
+        let listing = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(prefix)
+            .send()
+            .await?;
+        for object in listing.contents() {
+            if let Some(key) = object.key() {
+                self.client
+                    .delete_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .send()
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds the configured blob store for the `DUST_DATA_SOURCES_BUCKET` bucket. Defaults to GCS
+/// (the historical backend) unless `DUST_DATA_SOURCES_BLOB_STORE=s3` is set.
+async fn blob_store_from_env() -> Result<Arc<dyn DocumentBlobStore>> {
+    let bucket = match std::env::var("DUST_DATA_SOURCES_BUCKET") {
+        Ok(bucket) => bucket,
+        Err(_) => Err(anyhow!("DUST_DATA_SOURCES_BUCKET is not set"))?,
+    };
+
+    match std::env::var("DUST_DATA_SOURCES_BLOB_STORE").as_deref() {
+        Ok("s3") => Ok(Arc::new(S3BlobStore::from_env(bucket).await?)),
+        Ok("gcs") | Err(_) => Ok(Arc::new(GcsBlobStore::new(bucket))),
+        Ok(other) => Err(anyhow!("Unknown DUST_DATA_SOURCES_BLOB_STORE: {}", other)),
+    }
+}
+
+impl DataSource {
+    pub fn new(project: &Project, data_source_id: &str, config: &DataSourceConfig) -> Self {
+        DataSource {
+            project: project.clone(),
+            created: utils::now(),
+            data_source_id: data_source_id.to_string(),
+            internal_id: utils::new_id(),
+            config: config.clone(),
+        }
+    }
+
+    pub fn new_from_store(
+        project: &Project,
+        created: u64,
+        data_source_id: &str,
+        internal_id: &str,
+        config: &DataSourceConfig,
+    ) -> Self {
+        DataSource {
+            project: project.clone(),
+            created,
+            data_source_id: data_source_id.to_string(),
+            internal_id: internal_id.to_string(),
+            config: config.clone(),
+        }
+    }
+
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    pub fn data_source_id(&self) -> &str {
+        &self.data_source_id
+    }
+
+    pub fn internal_id(&self) -> &str {
+        &self.internal_id
+    }
+
+    pub fn config(&self) -> &DataSourceConfig {
+        &self.config
+    }
+
+    fn qdrant_collection(&self) -> String {
+        format!("ds_{}", self.internal_id)
+    }
+
+    async fn qdrant_client(&self) -> Result<QdrantClient> {
+        match std::env::var("QDRANT_URL") {
+            Ok(url) => {
+                let mut config = QdrantClientConfig::from_url(&url);
+                match std::env::var("QDRANT_API_KEY") {
+                    Ok(api_key) => {
+                        config.set_api_key(&api_key);
+                        QdrantClient::new(Some(config))
+                    }
+                    Err(_) => Err(anyhow!("QDRANT_API_KEY is not set"))?,
+                }
+            }
+            Err(_) => Err(anyhow!("QDRANT_URL is not set"))?,
+        }
+    }
+
+    async fn blob_store(&self) -> Result<Arc<dyn DocumentBlobStore>> {
+        blob_store_from_env().await
+    }
+
+    /// Before a document's points are deleted (re-upsert or `delete_document`), any point that is
+    /// a dedup canonical for other documents (non-empty `chunk_refs`) must not simply disappear:
+    /// promote the first referencing document to take over the point — reassigning
+    /// `document_id`/`document_id_hash`/`chunk_offset` to it and dropping it from `chunk_refs` —
+    /// so the remaining referencing documents (if any) keep a working canonical point instead of
+    /// a dangling `ChunkRef` that would otherwise hard-fail `search()` for every one of them.
+    ///
+    /// This leaves the point's `tags`/`timestamp`/`source_url`/`geo` untouched, which is correct:
+    /// `upsert` only ever dedups a chunk against a canonical candidate whose filterable payload
+    /// already matches the new document's (see `payload_matches_filter_metadata`), so every
+    /// document referencing this point — including the one being promoted — shares that payload.
+    async fn promote_canonical_points(
+        &self,
+        qdrant_client: &QdrantClient,
+        document_id_hash: &str,
+    ) -> Result<()> {
+        let results = qdrant_client
+            .scroll(&qdrant::ScrollPoints {
+                collection_name: self.qdrant_collection(),
+                filter: Some(qdrant::Filter {
+                    must: vec![qdrant::FieldCondition {
+                        key: "document_id_hash".to_string(),
+                        r#match: Some(qdrant::Match {
+                            match_value: Some(qdrant::r#match::MatchValue::Keyword(
+                                document_id_hash.to_string(),
+                            )),
+                        }),
+                        ..Default::default()
+                    }
+                    .into()],
+                    ..Default::default()
+                }),
+                with_payload: Some(true.into()),
+                ..Default::default()
+            })
+            .await?;
+
+        for r in results.result.iter() {
+            let (_, chunk) = parse_chunk_result(&r.payload, None)?;
+            if chunk.dedup_refs.is_empty() {
+                continue;
+            }
+
+            let mut remaining_refs = chunk.dedup_refs.clone();
+            let promoted = remaining_refs.remove(0);
+
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(promoted.document_id.as_bytes());
+            let promoted_document_id_hash = format!("{}", hasher.finalize().to_hex());
+
+            let mut payload = Payload::new();
+            payload.insert("document_id", promoted.document_id.clone());
+            payload.insert("document_id_hash", promoted_document_id_hash);
+            payload.insert("chunk_offset", promoted.offset as i64);
+            payload.insert("chunk_refs", serde_json::to_string(&remaining_refs)?);
+
+            qdrant_client
+                .set_payload(
+                    self.qdrant_collection(),
+                    &PointsSelector {
+                        // `content_hash` alone can match more than one canonical point (distinct
+                        // documents whose filter metadata diverges keep separate canonical points
+                        // sharing a `content_hash`), so also scope by this point's own
+                        // `document_id_hash` — the one this scroll already filtered on — to avoid
+                        // mutating the wrong point.
+                        points_selector_one_of: Some(PointsSelectorOneOf::Filter(Filter {
+                            must: vec![
+                                qdrant::FieldCondition {
+                                    key: "content_hash".to_string(),
+                                    r#match: Some(qdrant::Match {
+                                        match_value: Some(qdrant::r#match::MatchValue::Keyword(
+                                            chunk.content_hash.clone(),
+                                        )),
+                                    }),
+                                    ..Default::default()
+                                }
+                                .into(),
+                                qdrant::FieldCondition {
+                                    key: "document_id_hash".to_string(),
+                                    r#match: Some(qdrant::Match {
+                                        match_value: Some(qdrant::r#match::MatchValue::Keyword(
+                                            document_id_hash.to_string(),
+                                        )),
+                                    }),
+                                    ..Default::default()
+                                }
+                                .into(),
+                            ],
+                            ..Default::default()
+                        })),
+                    },
+                    payload,
+                    None,
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Before a document's own points are deleted (re-upsert or `delete_document`), strip this
+    /// document out of every other canonical point's `chunk_refs`, wherever it appears as a
+    /// referrer. `promote_canonical_points` only repairs the *canonical* side, keyed on the
+    /// deleted document's own `document_id_hash`; it has no way to see points owned by other
+    /// documents that merely reference this one. Left alone, a stale `ChunkRef` would keep
+    /// `search()` fanning another document's match out to this document, returning a chunk (and
+    /// its text) this document no longer contains.
+    ///
+    /// No payload field indexes `chunk_refs` by referrer, so this scans the whole collection page
+    /// by page (same pattern as `cmd_analyze`), rewriting only the points that actually reference
+    /// `document_id`.
+    async fn strip_referrer_chunk_refs(
+        &self,
+        qdrant_client: &QdrantClient,
+        document_id: &str,
+    ) -> Result<()> {
+        let mut offset = None;
+        loop {
+            let resp = qdrant_client
+                .scroll(&qdrant::ScrollPoints {
+                    collection_name: self.qdrant_collection(),
+                    filter: None,
+                    limit: Some(256),
+                    offset,
+                    with_payload: Some(true.into()),
+                    with_vectors: Some(false.into()),
+                    ..Default::default()
+                })
+                .await?;
+
+            for r in resp.result.iter() {
+                let (_, chunk) = parse_chunk_result(&r.payload, None)?;
+                if !chunk.dedup_refs.iter().any(|cr| cr.document_id == document_id) {
+                    continue;
+                }
+
+                let point_document_id_hash = match r.payload.get("document_id_hash") {
+                    Some(t) => match &t.kind {
+                        Some(qdrant::value::Kind::StringValue(s)) => s.clone(),
+                        _ => continue,
+                    },
+                    None => continue,
+                };
+                let remaining_refs = chunk
+                    .dedup_refs
+                    .into_iter()
+                    .filter(|cr| cr.document_id != document_id)
+                    .collect::<Vec<_>>();
+
+                let mut payload = Payload::new();
+                payload.insert("chunk_refs", serde_json::to_string(&remaining_refs)?);
+                qdrant_client
+                    .set_payload(
+                        self.qdrant_collection(),
+                        &PointsSelector {
+                            // Scope by the point's own `document_id_hash` in addition to
+                            // `content_hash`, since distinct canonical points can share a
+                            // `content_hash` (see `payload_matches_filter_metadata`).
+                            points_selector_one_of: Some(PointsSelectorOneOf::Filter(Filter {
+                                must: vec![
+                                    qdrant::FieldCondition {
+                                        key: "content_hash".to_string(),
+                                        r#match: Some(qdrant::Match {
+                                            match_value: Some(
+                                                qdrant::r#match::MatchValue::Keyword(
+                                                    chunk.content_hash.clone(),
+                                                ),
+                                            ),
+                                        }),
+                                        ..Default::default()
+                                    }
+                                    .into(),
+                                    qdrant::FieldCondition {
+                                        key: "document_id_hash".to_string(),
+                                        r#match: Some(qdrant::Match {
+                                            match_value: Some(
+                                                qdrant::r#match::MatchValue::Keyword(
+                                                    point_document_id_hash,
+                                                ),
+                                            ),
+                                        }),
+                                        ..Default::default()
+                                    }
+                                    .into(),
+                                ],
+                                ..Default::default()
+                            })),
+                        },
+                        payload,
+                        None,
+                    )
+                    .await?;
+            }
+
+            offset = resp.next_page_offset;
+            if offset.is_none() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn setup(&self, credentials: Credentials) -> Result<()> {
+        let mut embedder = provider(self.config.provider_id).embedder(self.config.model_id.clone());
+        embedder.initialize(credentials).await?;
+
+        // Store created data to smoke-test the configured blob store.
+        let bucket_path = format!("{}/{}", self.project.project_id(), self.internal_id);
+        let data_source_created_path = format!("{}/created.txt", bucket_path);
+
+        self.blob_store()
+            .await?
+            .upload(
+                &data_source_created_path,
+                format!("{}", self.created).as_bytes().to_vec(),
+                "application/text",
+            )
+            .await?;
+
+        utils::done(&format!(
+            "Created data_source blob store entry for `{}`",
             self.data_source_id
         ));
 
@@ -306,6 +1734,7 @@ impl DataSource {
                 }),
                 // We keep the entire payload on disk and index on document_id and tags.
                 on_disk_payload: Some(true),
+                quantization_config: self.config.quantization.as_ref().map(|q| q.to_qdrant()),
                 ..Default::default()
             })
             .await?;
@@ -340,16 +1769,62 @@ impl DataSource {
             )
             .await?;
 
-        utils::done(&format!(
-            "Created Qdrant collection and indexes for data_source `{}`",
-            self.data_source_id
-        ));
-
-        Ok(())
-    }
-
-    pub async fn update_tags(
-        &self,
+        // Full-text index on the chunk `text` payload field, used by hybrid search to run a
+        // keyword match alongside the dense vector search.
+        let _ = qdrant_client
+            .create_field_index(
+                self.qdrant_collection(),
+                "text",
+                qdrant::FieldType::Text,
+                None,
+                None,
+            )
+            .await?;
+
+        // Indexed so `FilterExpr::SourceUrl` can filter on it.
+        let _ = qdrant_client
+            .create_field_index(
+                self.qdrant_collection(),
+                "source_url",
+                qdrant::FieldType::Keyword,
+                None,
+                None,
+            )
+            .await?;
+
+        // Indexed so `FilterExpr::Geo` can filter on documents upserted with a `location`.
+        let _ = qdrant_client
+            .create_field_index(
+                self.qdrant_collection(),
+                "geo",
+                qdrant::FieldType::Geo,
+                None,
+                None,
+            )
+            .await?;
+
+        // Indexed so `upsert` can look up existing canonical points by content hash when
+        // deduplicating chunks across documents.
+        let _ = qdrant_client
+            .create_field_index(
+                self.qdrant_collection(),
+                "content_hash",
+                qdrant::FieldType::Keyword,
+                None,
+                None,
+            )
+            .await?;
+
+        utils::done(&format!(
+            "Created Qdrant collection and indexes for data_source `{}`",
+            self.data_source_id
+        ));
+
+        Ok(())
+    }
+
+    pub async fn update_tags(
+        &self,
         store: Box<dyn Store + Sync + Send>,
         document_id: String,
         add_tags: Vec<String>,
@@ -402,6 +1877,8 @@ impl DataSource {
         source_url: &Option<String>,
         text: &str,
         preserve_system_tags: bool,
+        location: &Option<GeoPoint>,
+        metadata: &HashMap<String, String>,
     ) -> Result<Document> {
         // disallow preserve_system_tags=true if tags contains a string starting with the system tag prefix
         // prevents having duplicate system tags or have users accidentally add system tags (from UI/API)
@@ -461,6 +1938,16 @@ impl DataSource {
         tags.iter().for_each(|tag| {
             hasher.update(tag.as_bytes());
         });
+        if let Some(location) = location {
+            hasher.update(format!("{}", location.lat).as_bytes());
+            hasher.update(format!("{}", location.lon).as_bytes());
+        }
+        let mut sorted_metadata = metadata.iter().collect::<Vec<_>>();
+        sorted_metadata.sort_by(|a, b| a.0.cmp(b.0));
+        sorted_metadata.iter().for_each(|(k, v)| {
+            hasher.update(k.as_bytes());
+            hasher.update(v.as_bytes());
+        });
         let document_hash = format!("{}", hasher.finalize().to_hex());
 
         let mut hasher = blake3::Hasher::new();
@@ -475,13 +1962,12 @@ impl DataSource {
             source_url,
             &document_hash,
             text.len() as u64,
+            location,
+            metadata,
         )?;
 
-        // GCP store raw text and document_id.
-        let bucket = match std::env::var("DUST_DATA_SOURCES_BUCKET") {
-            Ok(bucket) => bucket,
-            Err(_) => Err(anyhow!("DUST_DATA_SOURCES_BUCKET is not set"))?,
-        };
+        // Store raw text and document_id in the configured blob store.
+        let blob_store = self.blob_store().await?;
 
         let bucket_path = format!(
             "{}/{}/{}",
@@ -496,28 +1982,20 @@ impl DataSource {
         let timestamp_path = format!("{}/{}/timestamp.txt", bucket_path, document_hash);
 
         let _ = try_join!(
-            Object::create(
-                &bucket,
-                document_id.as_bytes().to_vec(),
+            blob_store.upload(
                 &document_id_path,
+                document_id.as_bytes().to_vec(),
                 "application/text",
             ),
-            Object::create(
-                &bucket,
-                text.as_bytes().to_vec(),
-                &content_path,
-                "application/text",
-            ),
-            Object::create(
-                &bucket,
-                serde_json::to_string(&tags).unwrap().as_bytes().to_vec(),
+            blob_store.upload(&content_path, text.as_bytes().to_vec(), "application/text"),
+            blob_store.upload(
                 &tags_path,
+                serde_json::to_string(&tags).unwrap().as_bytes().to_vec(),
                 "application/json",
             ),
-            Object::create(
-                &bucket,
-                format!("{}", timestamp).as_bytes().to_vec(),
+            blob_store.upload(
                 &timestamp_path,
+                format!("{}", timestamp).as_bytes().to_vec(),
                 "application/text",
             ),
         )?;
@@ -527,6 +2005,36 @@ impl DataSource {
             self.data_source_id, document_id,
         ));
 
+        // Clean-up previous document chunks (vector search db) before looking for dedup
+        // candidates, so a document's own prior points never show up as an "other document" to
+        // dedup against when it is re-upserted with unchanged content.
+        let qdrant_client = self.qdrant_client().await?;
+        self.promote_canonical_points(&qdrant_client, &document_id_hash)
+            .await?;
+        self.strip_referrer_chunk_refs(&qdrant_client, document_id)
+            .await?;
+        let _ = qdrant_client
+            .delete_points(
+                self.qdrant_collection(),
+                &qdrant::Filter {
+                    must_not: vec![],
+                    should: vec![],
+                    must: vec![qdrant::FieldCondition {
+                        key: "document_id_hash".to_string(),
+                        r#match: Some(qdrant::Match {
+                            match_value: Some(qdrant::r#match::MatchValue::Keyword(
+                                document_id_hash.clone(),
+                            )),
+                        }),
+                        ..Default::default()
+                    }
+                    .into()],
+                }
+                .into(),
+                None,
+            )
+            .await?;
+
         // Split text in chunks.
         let splits = splitter(self.config.splitter_id)
             .split(
@@ -538,17 +2046,102 @@ impl DataSource {
             )
             .await?;
 
-        // Embed chunks with max concurrency of 24.
-        let e = futures::stream::iter(splits.into_iter().enumerate())
+        // Content-address each split so identical chunks (shared boilerplate, repeated passages)
+        // can be deduplicated across documents: look up which splits already have a canonical
+        // point elsewhere in the collection before paying to embed them again. The hash must cover
+        // whatever text actually gets embedded, not the raw split: with `embedding_template`
+        // configured (interpolating `document_id`/`source_url`/`tags`), two documents can share
+        // identical chunk text but render to different embedder input, so they must not collapse
+        // onto the same canonical vector.
+        let embedding_template = self.config.embedding_template.clone();
+        let splits_with_hash = splits
+            .into_iter()
+            .enumerate()
             .map(|(i, s)| {
+                let embed_text = match &embedding_template {
+                    Some(template) => {
+                        render_embedding_template(template, document_id, source_url, &tags, &s)
+                    }
+                    None => s.clone(),
+                };
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(embed_text.as_bytes());
+                let content_hash = format!("{}", hasher.finalize().to_hex());
+                (i, s, content_hash)
+            })
+            .collect::<Vec<_>>();
+
+        let dedup_candidates = qdrant_client
+            .scroll(&qdrant::ScrollPoints {
+                collection_name: self.qdrant_collection(),
+                filter: Some(qdrant::Filter {
+                    must: vec![qdrant::FieldCondition {
+                        key: "content_hash".to_string(),
+                        r#match: Some(qdrant::Match {
+                            match_value: Some(qdrant::r#match::MatchValue::Keywords(
+                                qdrant::RepeatedStrings {
+                                    strings: splits_with_hash
+                                        .iter()
+                                        .map(|(_, _, h)| h.clone())
+                                        .collect(),
+                                },
+                            )),
+                        }),
+                        ..Default::default()
+                    }
+                    .into()],
+                    ..Default::default()
+                }),
+                limit: Some(splits_with_hash.len() as u32),
+                with_payload: Some(true.into()),
+                ..Default::default()
+            })
+            .await?;
+
+        // content_hash -> (canonical document_id, canonical offset, existing chunk_refs)
+        let mut canonical_by_content_hash: HashMap<String, (String, usize, Vec<ChunkRef>)> =
+            HashMap::new();
+        for r in dedup_candidates.result.iter() {
+            // A shared point's payload carries exactly one tags/timestamp/source_url/geo — don't
+            // dedup against a candidate whose filterable metadata diverges from this document's,
+            // or a tag/timestamp/geo-filtered search would silently miss this document's chunk.
+            if !payload_matches_filter_metadata(&r.payload, &tags, timestamp, source_url, location) {
+                continue;
+            }
+            let (document_id, chunk) = parse_chunk_result(&r.payload, None)?;
+            canonical_by_content_hash
+                .entry(chunk.content_hash.clone())
+                .or_insert((document_id, chunk.offset, chunk.dedup_refs));
+        }
+
+        let (to_embed, deduped): (Vec<_>, Vec<_>) = splits_with_hash
+            .into_iter()
+            .partition(|(_, _, content_hash)| !canonical_by_content_hash.contains_key(content_hash));
+
+        // Embed only the chunks that have no existing canonical point, with max concurrency of 24.
+        let e = futures::stream::iter(to_embed)
+            .map(|(i, s, content_hash)| {
                 let provider_id = self.config.provider_id.clone();
                 let model_id = self.config.model_id.clone();
                 let credentials = credentials.clone();
                 let extras = self.config.extras.clone();
+                // Render the configured template (if any) to build the embedder input; the raw
+                // chunk `s` is still what gets stored and returned.
+                let embed_text = match &embedding_template {
+                    Some(template) => {
+                        render_embedding_template(template, document_id, source_url, &tags, &s)
+                    }
+                    None => s.clone(),
+                };
                 tokio::spawn(async move {
-                    let r = EmbedderRequest::new(provider_id, &model_id, &s, extras);
+                    let r = EmbedderRequest::new(provider_id, &model_id, &embed_text, extras);
                     let v = r.execute(credentials).await?;
-                    Ok::<(usize, std::string::String, EmbedderVector), anyhow::Error>((i, s, v))
+                    Ok::<(usize, std::string::String, String, EmbedderVector), anyhow::Error>((
+                        i,
+                        s,
+                        content_hash,
+                        v,
+                    ))
                 })
             })
             .buffer_unordered(24)
@@ -566,9 +2159,9 @@ impl DataSource {
             e.len(),
         ));
 
-        document.chunks = e
+        let mut unique_chunks = e
             .into_iter()
-            .map(|(i, s, v)| {
+            .map(|(i, s, content_hash, v)| {
                 let mut hasher = blake3::Hasher::new();
                 hasher.update(document_hash.as_bytes());
                 hasher.update(s.as_bytes());
@@ -580,40 +2173,123 @@ impl DataSource {
                     offset: i,
                     vector: Some(v.vector),
                     score: None,
+                    score_details: None,
+                    content_hash,
+                    dedup_refs: vec![],
                 }
             })
             .collect::<Vec<_>>();
-        document.chunk_count = document.chunks.len();
-        document.token_count = Some(document.chunks.len() * self.config.max_chunk_size);
 
-        // Clean-up previous document chunks (vector search db).
-        let qdrant_client = self.qdrant_client().await?;
-        let _ = qdrant_client
-            .delete_points(
-                self.qdrant_collection(),
-                &qdrant::Filter {
-                    must_not: vec![],
-                    should: vec![],
-                    must: vec![qdrant::FieldCondition {
-                        key: "document_id_hash".to_string(),
-                        r#match: Some(qdrant::Match {
-                            match_value: Some(qdrant::r#match::MatchValue::Keyword(
-                                document_id_hash.clone(),
-                            )),
-                        }),
-                        ..Default::default()
-                    }
-                    .into()],
+        let deduped_chunks = deduped
+            .into_iter()
+            .map(|(i, s, content_hash)| {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(document_hash.as_bytes());
+                hasher.update(s.as_bytes());
+                let hash = format!("{}", hasher.finalize().to_hex());
+
+                Chunk {
+                    text: s,
+                    hash,
+                    offset: i,
+                    vector: None,
+                    score: None,
+                    score_details: None,
+                    content_hash,
+                    dedup_refs: vec![],
                 }
-                .into(),
-                None,
-            )
-            .await?;
+            })
+            .collect::<Vec<_>>();
+
+        let total_chunk_count = unique_chunks.len() + deduped_chunks.len();
+        let reused_bytes: usize = deduped_chunks.iter().map(|c| c.text.len()).sum();
+
+        utils::done(&format!(
+            "Dedup: data_source_id={} document_id={} total_chunks={} unique_chunks={} reused_chunks={} embeddings_saved={} bytes_saved={}",
+            self.data_source_id,
+            document_id,
+            total_chunk_count,
+            unique_chunks.len(),
+            deduped_chunks.len(),
+            deduped_chunks.len(),
+            reused_bytes,
+        ));
+
+        // Point each deduped chunk's canonical point back at this document so search can fan the
+        // single stored point back out to every document that references it.
+        for c in deduped_chunks.iter() {
+            let (canonical_document_id, canonical_offset, mut refs) = canonical_by_content_hash
+                .get(&c.content_hash)
+                .cloned()
+                .ok_or_else(|| anyhow!("Missing dedup canonical for content_hash {}", c.content_hash))?;
+            refs.push(ChunkRef {
+                document_id: document.document_id.clone(),
+                offset: c.offset,
+            });
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(canonical_document_id.as_bytes());
+            let canonical_document_id_hash = format!("{}", hasher.finalize().to_hex());
+
+            let mut payload = Payload::new();
+            payload.insert("chunk_refs", serde_json::to_string(&refs)?);
+            qdrant_client
+                .set_payload(
+                    self.qdrant_collection(),
+                    &PointsSelector {
+                        // `content_hash` alone isn't unique: two documents with identical chunk
+                        // text but divergent filter metadata intentionally get distinct canonical
+                        // points sharing the same `content_hash` (see
+                        // `payload_matches_filter_metadata`), so also scope by the canonical
+                        // point's own `document_id_hash` to avoid mutating the wrong one.
+                        points_selector_one_of: Some(PointsSelectorOneOf::Filter(Filter {
+                            must: vec![
+                                qdrant::FieldCondition {
+                                    key: "content_hash".to_string(),
+                                    r#match: Some(qdrant::Match {
+                                        match_value: Some(qdrant::r#match::MatchValue::Keyword(
+                                            c.content_hash.clone(),
+                                        )),
+                                    }),
+                                    ..Default::default()
+                                }
+                                .into(),
+                                qdrant::FieldCondition {
+                                    key: "document_id_hash".to_string(),
+                                    r#match: Some(qdrant::Match {
+                                        match_value: Some(qdrant::r#match::MatchValue::Keyword(
+                                            canonical_document_id_hash,
+                                        )),
+                                    }),
+                                    ..Default::default()
+                                }
+                                .into(),
+                            ],
+                            ..Default::default()
+                        })),
+                    },
+                    payload,
+                    None,
+                )
+                .await?;
+            // Keep the in-memory canonical map consistent in case the same content_hash shows up
+            // more than once within this document's splits.
+            canonical_by_content_hash.insert(
+                c.content_hash.clone(),
+                (canonical_document_id, canonical_offset, refs),
+            );
+        }
+
+        unique_chunks.extend(deduped_chunks);
+        unique_chunks.sort_by_key(|c| c.offset);
+        document.chunks = unique_chunks;
+        document.chunk_count = document.chunks.len();
+        document.token_count = Some(document.chunks.len() * self.config.max_chunk_size);
 
-        // Insert new chunks (vector search db).
+        // Insert new chunks (vector search db) — only unique (non-deduped) chunks get a new point.
         let points = document
             .chunks
             .iter()
+            .filter(|c| c.vector.is_some())
             .map(|c| {
                 let uid = Uuid::new_v4();
                 let mut payload = Payload::new();
@@ -621,10 +2297,39 @@ impl DataSource {
                 payload.insert("timestamp", document.timestamp as i64);
                 payload.insert("chunk_offset", c.offset as i64);
                 payload.insert("chunk_hash", c.hash.clone());
+                payload.insert("content_hash", c.content_hash.clone());
                 payload.insert("data_source_id", self.data_source_id.clone());
                 payload.insert("data_source_internal_id", self.internal_id.clone());
                 payload.insert("document_id", document.document_id.clone());
                 payload.insert("document_id_hash", document_id_hash.clone());
+                payload.insert("source_url", document.source_url.clone().unwrap_or_default());
+                if let Some(location) = &document.location {
+                    payload.insert(
+                        "geo",
+                        qdrant::Value {
+                            kind: Some(qdrant::value::Kind::StructValue(qdrant::Struct {
+                                fields: HashMap::from([
+                                    (
+                                        "lat".to_string(),
+                                        qdrant::Value {
+                                            kind: Some(qdrant::value::Kind::DoubleValue(
+                                                location.lat,
+                                            )),
+                                        },
+                                    ),
+                                    (
+                                        "lon".to_string(),
+                                        qdrant::Value {
+                                            kind: Some(qdrant::value::Kind::DoubleValue(
+                                                location.lon,
+                                            )),
+                                        },
+                                    ),
+                                ]),
+                            })),
+                        },
+                    );
+                }
                 payload.insert("text", c.text.clone());
 
                 qdrant::PointStruct::new(
@@ -656,21 +2361,137 @@ impl DataSource {
             .upsert_data_source_document(&self.project, &self.data_source_id, &document)
             .await?;
 
+        // Re-index the document's `metadata` key/value pairs so `cmd_list --where key=value` can
+        // look up matching documents directly rather than scanning every document's metadata.
+        store
+            .index_data_source_document_metadata(
+                &self.project,
+                &self.data_source_id,
+                document_id,
+                &document.metadata,
+            )
+            .await?;
+
         Ok(document)
     }
 
     const MAX_TOP_K_SEARCH: usize = 128;
 
+    // Oversample factor applied to `top_k` when fetching keyword match candidates, since the
+    // Qdrant full-text filter returns matches unordered and we need enough of them to rank and
+    // fuse meaningfully against the dense vector results.
+    const KEYWORD_OVERSAMPLE: u64 = 4;
+
+    /// Runs a keyword match over the indexed `text` payload field and ranks the matches
+    /// client-side by `keyword_term_score`, since Qdrant's full-text filter itself only matches,
+    /// it does not score. Returns the top `top_k` candidates as `(document_id, Chunk)` pairs with
+    /// `Chunk.score` set to that `keyword_term_score`.
+    async fn search_keyword(
+        &self,
+        qdrant_client: &QdrantClient,
+        query: &str,
+        filter: &Option<qdrant::Filter>,
+        top_k: usize,
+    ) -> Result<Vec<(String, Chunk)>> {
+        // Nest the caller's full filter (must/must_not/should) as a sub-condition alongside the
+        // text match, rather than only copying its `must` vector: an `Or`/`Not` filter lowers to a
+        // Qdrant `Filter` whose `must` is empty, so pulling just `must` out would silently drop it
+        // and let excluded chunks leak back in through this leg's results.
+        let mut must = vec![qdrant::FieldCondition {
+            key: "text".to_string(),
+            r#match: Some(qdrant::Match {
+                match_value: Some(qdrant::r#match::MatchValue::Text(query.to_string())),
+            }),
+            ..Default::default()
+        }
+        .into()];
+        if let Some(f) = filter.clone() {
+            must.push(qdrant::Condition {
+                condition_one_of: Some(qdrant::condition::ConditionOneOf::Filter(f)),
+            });
+        }
+
+        let results = qdrant_client
+            .scroll(&qdrant::ScrollPoints {
+                collection_name: self.qdrant_collection(),
+                filter: Some(qdrant::Filter {
+                    must,
+                    ..Default::default()
+                }),
+                limit: Some(top_k as u32 * DataSource::KEYWORD_OVERSAMPLE as u32),
+                with_payload: Some(true.into()),
+                ..Default::default()
+            })
+            .await?;
+
+        let query_terms = query
+            .split_whitespace()
+            .map(|t| t.to_lowercase())
+            .collect::<Vec<_>>();
+
+        let mut candidates = results
+            .result
+            .iter()
+            .map(|r| parse_chunk_result(&r.payload, None))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .map(|(document_id, mut chunk)| {
+                chunk.score = Some(keyword_term_score(&chunk.text, &query_terms));
+                (document_id, chunk)
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| {
+            b.1.score
+                .unwrap_or(0.0)
+                .partial_cmp(&a.1.score.unwrap_or(0.0))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        candidates.truncate(top_k);
+
+        Ok(candidates)
+    }
+
     pub async fn search(
         &self,
         credentials: Credentials,
         store: Box<dyn Store + Sync + Send>,
         query: &str,
         top_k: usize,
-        filter: Option<SearchFilter>,
+        filter: Option<FilterExpr>,
+        full_text: bool,
+        target_document_tokens: Option<usize>,
+        rank_by: Option<Vec<RankCriterion>>,
+    ) -> Result<Vec<Document>> {
+        self.search_instrumented(
+            credentials,
+            store,
+            query,
+            top_k,
+            filter,
+            full_text,
+            target_document_tokens,
+            rank_by,
+            None,
+        )
+        .await
+    }
+
+    /// Same as `search`, but fills in `timing` with per-stage wall-clock timings (Qdrant dense
+    /// search, keyword search, blob fetch, and document-expansion scroll) when provided. Used by
+    /// `cmd_bench` to detect per-stage regressions instead of just end-to-end latency.
+    pub async fn search_instrumented(
+        &self,
+        credentials: Credentials,
+        store: Box<dyn Store + Sync + Send>,
+        query: &str,
+        top_k: usize,
+        filter: Option<FilterExpr>,
         full_text: bool,
         target_document_tokens: Option<usize>,
+        rank_by: Option<Vec<RankCriterion>>,
+        mut timing: Option<&mut SearchTiming>,
     ) -> Result<Vec<Document>> {
+        let search_t0 = std::time::Instant::now();
         if top_k > DataSource::MAX_TOP_K_SEARCH {
             return Err(anyhow!("top_k must be <= {}", DataSource::MAX_TOP_K_SEARCH));
         }
@@ -684,92 +2505,27 @@ impl DataSource {
         );
         let v = r.execute(credentials).await?;
 
-        // Construct the filters for the search query if specified.
-        let f = match filter {
-            Some(f) => {
-                let mut must_filter: Vec<qdrant::Condition> = vec![];
-                let mut must_not_filter: Vec<qdrant::Condition> = vec![];
+        // Construct the filter for the search query if specified. `FilterExpr` handles the
+        // recursive AND/OR/NOT lowering; the legacy flat `SearchFilter` shape arrives here already
+        // converted to a top-level `And` by its caller.
+        let f = filter.map(|expr| expr.to_qdrant_filter());
 
-                match f.tags {
-                    Some(tags) => {
-                        match tags.is_in.clone() {
-                            Some(v) => must_filter.push(
-                                qdrant::FieldCondition {
-                                    key: "tags".to_string(),
-                                    r#match: Some(qdrant::Match {
-                                        match_value: Some(qdrant::r#match::MatchValue::Keywords(
-                                            qdrant::RepeatedStrings { strings: v },
-                                        )),
-                                    }),
-                                    ..Default::default()
-                                }
-                                .into(),
-                            ),
-                            None => (),
-                        };
-                        match tags.is_not.clone() {
-                            Some(v) => must_not_filter.push(
-                                qdrant::FieldCondition {
-                                    key: "tags".to_string(),
-                                    r#match: Some(qdrant::Match {
-                                        match_value: Some(qdrant::r#match::MatchValue::Keywords(
-                                            qdrant::RepeatedStrings { strings: v },
-                                        )),
-                                    }),
-                                    ..Default::default()
-                                }
-                                .into(),
-                            ),
-                            None => (),
-                        };
-                    }
-                    None => (),
-                };
+        let keyword_filter = f.clone();
 
-                match f.timestamp {
-                    Some(timestamp) => {
-                        match timestamp.gt.clone() {
-                            Some(v) => must_filter.push(
-                                qdrant::FieldCondition {
-                                    key: "timestamp".to_string(),
-                                    range: Some(qdrant::Range {
-                                        gte: Some(v as f64),
-                                        ..Default::default()
-                                    }),
-                                    ..Default::default()
-                                }
-                                .into(),
-                            ),
-                            None => (),
-                        };
-                        match timestamp.lt.clone() {
-                            Some(v) => must_filter.push(
-                                qdrant::FieldCondition {
-                                    key: "timestamp".to_string(),
-                                    range: Some(qdrant::Range {
-                                        lte: Some(v as f64),
-                                        ..Default::default()
-                                    }),
-                                    ..Default::default()
-                                }
-                                .into(),
-                            ),
-                            None => (),
-                        };
-                    }
-                    None => (),
-                };
-
-                Some(qdrant::Filter {
-                    must: must_filter,
-                    must_not: must_not_filter,
-                    ..Default::default()
-                })
-            }
-            None => None,
-        };
+        // When the collection is quantized, oversample against the compressed codes and rescore
+        // the result against the original f32 vectors so we don't trade away recall for the
+        // memory savings.
+        let search_params = self.config.quantization.as_ref().map(|_| qdrant::SearchParams {
+            quantization: Some(qdrant::QuantizationSearchParams {
+                rescore: self.config.quantization_rescore.or(Some(true)),
+                oversampling: self.config.quantization_oversampling.or(Some(2.0)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
 
         let qdrant_client = self.qdrant_client().await?;
+        let qdrant_t0 = std::time::Instant::now();
         let results = qdrant_client
             .search_points(&qdrant::SearchPoints {
                 collection_name: self.qdrant_collection(),
@@ -777,7 +2533,7 @@ impl DataSource {
                 filter: f,
                 limit: top_k as u64,
                 with_payload: Some(true.into()),
-                params: None,
+                params: search_params,
                 score_threshold: None,
                 offset: None,
                 vector_name: None,
@@ -785,80 +2541,169 @@ impl DataSource {
                 read_consistency: None,
             })
             .await?;
+        if let Some(t) = timing.as_deref_mut() {
+            t.qdrant_ms = qdrant_t0.elapsed().as_millis();
+        }
 
-        let chunks = results
+        let dense_chunks = results
             .result
             .iter()
-            .map(|r| {
-                let document_id = match r.payload.get("document_id") {
-                    Some(t) => match t.kind {
-                        Some(qdrant::value::Kind::StringValue(ref s)) => s.clone(),
-                        _ => Err(anyhow!("Missing `document_id` in chunk payload"))?,
-                    },
-                    None => Err(anyhow!("Missing `document_id` in chunk payload"))?,
-                };
-                let text = match r.payload.get("text") {
-                    Some(t) => match t.kind {
-                        Some(qdrant::value::Kind::StringValue(ref s)) => s,
-                        _ => Err(anyhow!("Missing `text` in chunk payload"))?,
-                    },
-                    None => Err(anyhow!("Missing `text` in chunk payload"))?,
-                };
-                let chunk_hash = match r.payload.get("chunk_hash") {
-                    Some(t) => match t.kind {
-                        Some(qdrant::value::Kind::StringValue(ref s)) => s,
-                        _ => Err(anyhow!("Missing `chunk_hash` in chunk payload"))?,
-                    },
-                    None => Err(anyhow!("Missing `chunk_hash` in chunk payload"))?,
-                };
-                let chunk_offset = match r.payload.get("chunk_offset") {
-                    Some(t) => match t.kind {
-                        Some(qdrant::value::Kind::IntegerValue(i)) => i,
-                        _ => Err(anyhow!("Missing `chunk_offset` in chunk payload"))?,
-                    },
-                    None => Err(anyhow!("Missing `chunk_offset` in chunk payload"))?,
-                };
-                Ok((
-                    document_id,
-                    Chunk {
-                        text: text.clone(),
-                        hash: chunk_hash.clone(),
-                        offset: chunk_offset as usize,
-                        vector: None,
-                        score: Some(r.score as f64),
-                    },
-                ))
-            })
+            .map(|r| parse_chunk_result(&r.payload, Some(r.score as f64)))
             .collect::<Result<Vec<_>>>()?;
 
+        // Per-chunk ranking breakdown, keyed by `chunk_hash`, assembled as each sub-ranker runs and
+        // attached to the final `chunks` below so API consumers can see why a chunk ranked where
+        // it did instead of just its opaque final `score`.
+        let mut score_details_by_hash: HashMap<String, ChunkScoreDetails> = HashMap::new();
+        for (rank, (_, chunk)) in dense_chunks.iter().enumerate() {
+            let details = score_details_by_hash.entry(chunk.hash.clone()).or_default();
+            details.semantic_score = chunk.score;
+            details.semantic_rank = Some(rank + 1);
+        }
+
+        // In hybrid mode, also run a keyword match over the indexed `text` payload field and combine
+        // the two ranked lists according to the resolved `SearchMode` (Reciprocal Rank Fusion by
+        // default) so `Chunk.score` can reflect both lexical and semantic relevance.
+        let chunks = if full_text {
+            let keyword_t0 = std::time::Instant::now();
+            let keyword_chunks = self
+                .search_keyword(&qdrant_client, query, &keyword_filter, top_k)
+                .await?;
+            if let Some(t) = timing.as_deref_mut() {
+                t.keyword_ms = keyword_t0.elapsed().as_millis();
+            }
+
+            for (rank, (_, chunk)) in keyword_chunks.iter().enumerate() {
+                let details = score_details_by_hash.entry(chunk.hash.clone()).or_default();
+                details.keyword_score = chunk.score;
+                details.keyword_rank = Some(rank + 1);
+            }
+
+            // Resolve the effective mode: an explicit `search_mode` wins, `semantic_ratio` is a
+            // shorthand for `Hybrid`, and the default is RRF with the standard constant.
+            let mode = match self.config.search_mode.clone() {
+                Some(mode) => mode,
+                None => match self.config.semantic_ratio {
+                    Some(alpha) => SearchMode::Hybrid { alpha },
+                    None => SearchMode::Rrf { k: RRF_K },
+                },
+            };
+
+            match mode {
+                SearchMode::SemanticOnly => dense_chunks,
+                // Tunable weighting: normalize both score scales and blend them directly rather
+                // than fusing ranks, so callers can dial relevance between exact-term-heavy and
+                // conceptual queries.
+                SearchMode::Hybrid { alpha } => combine_weighted(
+                    dense_chunks,
+                    keyword_chunks,
+                    alpha,
+                    top_k,
+                    &mut score_details_by_hash,
+                ),
+                SearchMode::Rrf { k } => {
+                    let dense_ranking = dense_chunks
+                        .iter()
+                        .map(|(_, c)| c.hash.clone())
+                        .collect::<Vec<_>>();
+                    let keyword_ranking = keyword_chunks
+                        .iter()
+                        .map(|(_, c)| c.hash.clone())
+                        .collect::<Vec<_>>();
+                    let fused_scores = reciprocal_rank_fusion(&[dense_ranking, keyword_ranking], k);
+
+                    let mut by_hash: HashMap<String, (String, Chunk)> = HashMap::new();
+                    for (document_id, chunk) in
+                        dense_chunks.into_iter().chain(keyword_chunks.into_iter())
+                    {
+                        by_hash.entry(chunk.hash.clone()).or_insert((document_id, chunk));
+                    }
+
+                    let mut fused = by_hash
+                        .into_values()
+                        .map(|(document_id, mut chunk)| {
+                            chunk.score = fused_scores.get(&chunk.hash).cloned();
+                            (document_id, chunk)
+                        })
+                        .collect::<Vec<_>>();
+                    fused.sort_by(|a, b| {
+                        b.1.score
+                            .unwrap_or(0.0)
+                            .partial_cmp(&a.1.score.unwrap_or(0.0))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    });
+                    fused.truncate(top_k);
+                    fused
+                }
+            }
+        } else {
+            dense_chunks
+        };
+        let chunks = chunks
+            .into_iter()
+            .map(|(document_id, mut chunk)| {
+                let mut details = score_details_by_hash.remove(&chunk.hash).unwrap_or_default();
+                details.combined_score = chunk.score;
+                chunk.score_details = Some(details);
+                (document_id, chunk)
+            })
+            .collect::<Vec<_>>();
+
+        // A matched point may be a dedup canonical shared by other documents; fan it back out to
+        // every referencing document so recall isn't lost to dedup.
+        let chunks = chunks
+            .into_iter()
+            .flat_map(|(document_id, chunk)| {
+                let extra = chunk
+                    .dedup_refs
+                    .iter()
+                    .map(|r| {
+                        let mut c = chunk.clone();
+                        c.offset = r.offset;
+                        c.dedup_refs = vec![];
+                        (r.document_id.clone(), c)
+                    })
+                    .collect::<Vec<_>>();
+                std::iter::once((document_id, chunk)).chain(extra)
+            })
+            .collect::<Vec<_>>();
+
         // get a list of unique document_id
         let document_ids = chunks
             .iter()
             .map(|(document_id, _)| document_id.clone())
             .collect::<std::collections::HashSet<_>>();
 
-        // GCP retrieve raw text and document_id.
-        let bucket = match std::env::var("DUST_DATA_SOURCES_BUCKET") {
-            Ok(bucket) => bucket,
-            Err(_) => Err(anyhow!("DUST_DATA_SOURCES_BUCKET is not set"))?,
-        };
-
         // Retrieve the documents from the store.
+        let blob_store = self.blob_store().await?;
+
+        let blob_fetch_t0 = std::time::Instant::now();
         let documents = futures::stream::iter(document_ids)
             .map(|document_id| {
                 let store = store.clone();
                 let document_id = document_id.clone();
                 let data_source_id = self.data_source_id.clone();
                 let project = self.project.clone();
-                let bucket = bucket.clone();
+                let blob_store = blob_store.clone();
                 let internal_id = self.internal_id.clone();
                 tokio::spawn(async move {
+                    // A matched chunk can point at a document via a stale `ChunkRef` (its dedup
+                    // canonical was reassigned or deleted out from under it by a race with a
+                    // concurrent upsert/delete); tolerate that as a dropped result rather than
+                    // failing the whole search for every other document in the batch.
                     let mut d: Document = match store
                         .load_data_source_document(&project, &data_source_id, &document_id, &None)
                         .await?
                     {
                         Some(d) => d,
-                        None => Err(anyhow!("Document not found"))?,
+                        None => {
+                            utils::error(&format!(
+                                "Data source document not found, dropping from search results: \
+                                 document_id={}",
+                                document_id,
+                            ));
+                            return Ok::<Option<Document>, anyhow::Error>(None);
+                        }
                     };
 
                     if full_text {
@@ -873,12 +2718,12 @@ impl DataSource {
                             document_id_hash
                         );
                         let content_path = format!("{}/{}/content.txt", bucket_path, d.hash);
-                        let bytes = Object::download(&bucket, &content_path).await?;
+                        let bytes = blob_store.download(&content_path).await?;
                         let text = String::from_utf8(bytes)?;
 
                         d.text = Some(text.clone());
                     }
-                    Ok::<Document, anyhow::Error>(d)
+                    Ok::<Option<Document>, anyhow::Error>(Some(d))
                 })
             })
             .buffer_unordered(16)
@@ -886,12 +2731,19 @@ impl DataSource {
                 Err(e) => Err(anyhow!("Data source document retrieval error: {}", e))?,
                 Ok(r) => r,
             })
-            .try_collect::<Vec<_>>()
-            .await?;
+            .try_collect::<Vec<Option<Document>>>()
+            .await?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if let Some(t) = timing.as_deref_mut() {
+            t.blob_fetch_ms = blob_fetch_t0.elapsed().as_millis();
+        }
 
         // Qdrant client implements the sync and send traits, so we just need
         // to wrap it in an Arc so that it can be cloned.
         let l_qdrant_client = Arc::new(qdrant_client);
+        let expansion_t0 = std::time::Instant::now();
         let mut documents = match target_document_tokens {
             Some(target) => {
                 futures::stream::iter(documents)
@@ -1012,6 +2864,7 @@ impl DataSource {
                                 .into_iter()
                                 .map(|mut chunk| {
                                     let mut prepend = "".to_owned();
+                                    let mut expanded = false;
                                     while counter < parsed_results.len()
                                         && *new_offsets.get(&parsed_results[counter].1).unwrap()
                                             == chunk.offset
@@ -1029,8 +2882,18 @@ impl DataSource {
                                         }
                                         counter += 1;
                                         token_count += chunk_size;
+                                        expanded = true;
                                     }
                                     chunk.text = prepend + &chunk.text;
+                                    // The chunk itself is still a genuine match (reason stays
+                                    // `Match`); only its `text` gained free neighbor context, so
+                                    // flag that separately instead of relabeling the match.
+                                    if expanded {
+                                        chunk
+                                            .score_details
+                                            .get_or_insert_with(ChunkScoreDetails::default)
+                                            .context_expanded = true;
+                                    }
                                     chunk
                                 })
                                 .collect::<Vec<_>>();
@@ -1072,15 +2935,42 @@ impl DataSource {
                 })
                 .collect::<Vec<_>>(),
         };
+        if let Some(t) = timing.as_deref_mut() {
+            t.expansion_ms = expansion_t0.elapsed().as_millis();
+        }
 
-        // Sort the documents by the score of the first chunk (guaranteed ordered).
-        documents.sort_by(|a, b| {
-            let b_score = b.chunks.first().unwrap().score.unwrap_or(0.0);
-            let a_score = a.chunks.first().unwrap().score.unwrap_or(0.0);
-            b_score
-                .partial_cmp(&a_score)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
+        if let Some(criteria) = rank_by.as_ref() {
+            // Re-key and re-sort by the requested, ordered list of criteria rather than the
+            // default embedding-score order.
+            let query_terms = query
+                .split_whitespace()
+                .map(|t| t.to_lowercase())
+                .collect::<Vec<_>>();
+            for document in documents.iter_mut() {
+                let document_timestamp = document.timestamp;
+                let mut keyed = std::mem::take(&mut document.chunks)
+                    .into_iter()
+                    .map(|c| (rank_key(&c, document_timestamp, &query_terms), c))
+                    .collect::<Vec<_>>();
+                keyed.sort_by(|a, b| rank_key_cmp(&a.0, &b.0, criteria));
+                document.chunks = keyed.into_iter().map(|(_, c)| c).collect();
+            }
+            let mut keyed = std::mem::take(&mut documents)
+                .into_iter()
+                .map(|d| (rank_key(d.chunks.first().unwrap(), d.timestamp, &query_terms), d))
+                .collect::<Vec<_>>();
+            keyed.sort_by(|a, b| rank_key_cmp(&a.0, &b.0, criteria));
+            documents = keyed.into_iter().map(|(_, d)| d).collect();
+        } else {
+            // Sort the documents by the score of the first chunk (guaranteed ordered).
+            documents.sort_by(|a, b| {
+                let b_score = b.chunks.first().unwrap().score.unwrap_or(0.0);
+                let a_score = a.chunks.first().unwrap().score.unwrap_or(0.0);
+                b_score
+                    .partial_cmp(&a_score)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+        }
 
         utils::done(&format!(
             "Searched Data Source: data_source_id={} document_count={} chunk_count={}",
@@ -1088,6 +2978,9 @@ impl DataSource {
             documents.len(),
             documents.iter().map(|d| d.chunks.len()).sum::<usize>(),
         ));
+        if let Some(t) = timing.as_deref_mut() {
+            t.total_ms = search_t0.elapsed().as_millis();
+        }
 
         Ok(documents)
     }
@@ -1130,12 +3023,6 @@ impl DataSource {
         hasher.update(document_id.as_bytes());
         let document_id_hash = format!("{}", hasher.finalize().to_hex());
 
-        // GCP retrieve raw text and document_id.
-        let bucket = match std::env::var("DUST_DATA_SOURCES_BUCKET") {
-            Ok(bucket) => bucket,
-            Err(_) => Err(anyhow!("DUST_DATA_SOURCES_BUCKET is not set"))?,
-        };
-
         let bucket_path = format!(
             "{}/{}/{}",
             self.project.project_id(),
@@ -1143,7 +3030,7 @@ impl DataSource {
             document_id_hash
         );
         let content_path = format!("{}/{}/content.txt", bucket_path, d.hash);
-        let bytes = Object::download(&bucket, &content_path).await?;
+        let bytes = self.blob_store().await?.download(&content_path).await?;
         let text = String::from_utf8(bytes)?;
 
         d.text = Some(text.clone());
@@ -1164,6 +3051,10 @@ impl DataSource {
 
         // Clean-up document chunks (vector search db).
         let qdrant_client = self.qdrant_client().await?;
+        self.promote_canonical_points(&qdrant_client, &document_id_hash)
+            .await?;
+        self.strip_referrer_chunk_refs(&qdrant_client, document_id)
+            .await?;
         let _ = qdrant_client
             .delete_points(
                 self.qdrant_collection(),
@@ -1208,44 +3099,726 @@ impl DataSource {
             self.data_source_id,
         ));
 
-        // Delete Data Source and documents (SQL)
-        store
-            .delete_data_source(&self.project, &self.data_source_id)
-            .await?;
+        // Delete Data Source and documents (SQL)
+        store
+            .delete_data_source(&self.project, &self.data_source_id)
+            .await?;
+
+        utils::done(&format!(
+            "Deleted Data Source records: data_source_id={}",
+            self.data_source_id,
+        ));
+
+        Ok(())
+    }
+}
+
+/// Lifecycle of a queued `Task`, persisted alongside `created_at`/`updated_at` so callers can poll
+/// progress (`cmd_task_status`) instead of blocking on `cmd_upsert` directly (`--async`).
+/// `cmd_delete` has no queued mode and always runs synchronously.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Enqueued,
+    Processing,
+    Succeeded,
+    Failed,
+}
+
+impl TaskStatus {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "enqueued" => Ok(TaskStatus::Enqueued),
+            "processing" => Ok(TaskStatus::Processing),
+            "succeeded" => Ok(TaskStatus::Succeeded),
+            "failed" => Ok(TaskStatus::Failed),
+            other => Err(anyhow!(
+                "Unknown task status `{}`, expected one of `enqueued`, `processing`, \
+                 `succeeded`, `failed`",
+                other
+            )),
+        }
+    }
+}
+
+/// The work a queued `Task` replays once a worker picks it up — enough of `cmd_upsert`'s /
+/// `cmd_delete`'s arguments to run them standalone, since the originating CLI invocation has
+/// already returned by the time the worker runs.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TaskPayload {
+    Upsert {
+        timestamp: Option<u64>,
+        tags: Vec<String>,
+        source_url: Option<String>,
+        text_path: String,
+        location: Option<GeoPoint>,
+        metadata: HashMap<String, String>,
+    },
+    Delete,
+}
+
+/// A persisted ingestion task, as enqueued by `cmd_upsert --async` and drained by `cmd_worker`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Task {
+    pub task_id: String,
+    pub data_source_id: String,
+    pub document_id: String,
+    pub status: TaskStatus,
+    pub payload: TaskPayload,
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl Task {
+    fn new(data_source_id: &str, document_id: &str, payload: TaskPayload) -> Self {
+        let now = utils::now();
+        Task {
+            task_id: Uuid::new_v4().to_string(),
+            data_source_id: data_source_id.to_string(),
+            document_id: document_id.to_string(),
+            status: TaskStatus::Enqueued,
+            payload,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Runs the `TaskPayload` a queued `Task` was enqueued with, mirroring the body of `cmd_upsert`/
+/// `cmd_delete` so a worker-run task behaves exactly like the synchronous CLI path would have.
+async fn run_task(store: &SQLiteStore, project: &Project, task: &Task) -> Result<()> {
+    let ds = match store.load_data_source(project, &task.data_source_id).await? {
+        Some(ds) => ds,
+        None => Err(anyhow!("Data source `{}` not found", task.data_source_id))?,
+    };
+
+    match &task.payload {
+        TaskPayload::Upsert {
+            timestamp,
+            tags,
+            source_url,
+            text_path,
+            location,
+            metadata,
+        } => {
+            let text_path = shellexpand::tilde(text_path).into_owned();
+            let contents = async_fs::read(std::path::Path::new(&text_path)).await?;
+            let text = std::str::from_utf8(&contents)?;
+
+            ds.upsert(
+                Credentials::new(),
+                Box::new(store.clone()),
+                &task.document_id,
+                *timestamp,
+                tags,
+                source_url,
+                text,
+                true, // preserve system tags
+                location,
+                metadata,
+            )
+            .await?;
+        }
+        TaskPayload::Delete => {
+            ds.delete_document(Box::new(store.clone()), &task.document_id)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// An operation a `CapabilityToken` can be scoped to via a `Caveat::Operation` caveat.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum CapabilityOperation {
+    Search,
+    Upsert,
+    Delete,
+}
+
+/// A single caveat in a `CapabilityToken`'s signature chain, narrowing what the token authorizes.
+/// A holder can append caveats to narrow a token's scope without the root key; they cannot remove
+/// or alter an existing one without invalidating the final signature.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "op", content = "value", rename_all = "snake_case")]
+pub enum Caveat {
+    DataSource(String),
+    Operation(Vec<CapabilityOperation>),
+    ExpiresAt(u64),
+}
+
+/// A macaroon-style capability token: a root `identifier` plus a chain of signed `caveats`, each
+/// HMAC-chained over the previous signature (`sig_0 = HMAC(root_key, identifier)`, `sig_{i+1} =
+/// HMAC(sig_i, caveat_i)`). `verify` re-derives this chain from the root key and checks every
+/// caveat against the request context, so a deployment can hand out narrowly-scoped, time-limited
+/// tokens via `--credential` instead of all-or-nothing access.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CapabilityToken {
+    pub identifier: String,
+    pub caveats: Vec<Caveat>,
+    pub signature: String,
+}
+
+impl CapabilityToken {
+    /// Mints a root token with no caveats yet; narrow it with `with_caveat` before handing it out.
+    pub fn mint(identifier: &str, root_key: &[u8]) -> Self {
+        CapabilityToken {
+            identifier: identifier.to_string(),
+            caveats: vec![],
+            signature: Self::sign(root_key, identifier.as_bytes()),
+        }
+    }
+
+    /// Appends a caveat and re-chains the signature over it.
+    pub fn with_caveat(mut self, caveat: Caveat) -> Self {
+        let caveat_bytes = serde_json::to_vec(&caveat).unwrap_or_default();
+        self.signature = Self::sign(self.signature.as_bytes(), &caveat_bytes);
+        self.caveats.push(caveat);
+        self
+    }
+
+    fn sign(key: &[u8], message: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+        mac.update(message);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Re-derives the signature chain from `root_key` and checks every caveat against the request
+    /// context (`data_source_id`, the `operation` being run, and `now`), rejecting on signature
+    /// mismatch, scope mismatch, or expiry.
+    pub fn verify(
+        &self,
+        root_key: &[u8],
+        data_source_id: &str,
+        operation: CapabilityOperation,
+        now: u64,
+    ) -> Result<()> {
+        let mut sig = Self::sign(root_key, self.identifier.as_bytes());
+        for caveat in &self.caveats {
+            let caveat_bytes = serde_json::to_vec(caveat).unwrap_or_default();
+            sig = Self::sign(sig.as_bytes(), &caveat_bytes);
+        }
+        // Compare the decoded HMAC digests in constant time: a non-constant-time string/byte
+        // comparison here is a timing side-channel on the very thing that's supposed to make
+        // unauthorized upsert/search/delete impossible.
+        let expected = hex::decode(&sig).unwrap_or_default();
+        let actual = hex::decode(&self.signature).unwrap_or_default();
+        let signatures_match = expected.len() == actual.len()
+            && bool::from(subtle::ConstantTimeEq::ct_eq(
+                expected.as_slice(),
+                actual.as_slice(),
+            ));
+        if !signatures_match {
+            return Err(anyhow!("Credential signature verification failed"));
+        }
+
+        for caveat in &self.caveats {
+            match caveat {
+                Caveat::DataSource(expected) if expected != data_source_id => {
+                    return Err(anyhow!(
+                        "Credential is scoped to data_source `{}`, not `{}`",
+                        expected,
+                        data_source_id
+                    ));
+                }
+                Caveat::Operation(allowed) if !allowed.contains(&operation) => {
+                    return Err(anyhow!(
+                        "Credential does not authorize operation `{:?}`",
+                        operation
+                    ));
+                }
+                Caveat::ExpiresAt(expires_at) if now > *expires_at => {
+                    return Err(anyhow!("Credential expired at {}", expires_at));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serializes and base64-encodes the token into the opaque string format `--credential` takes.
+    pub fn encode(&self) -> Result<String> {
+        Ok(base64::encode(serde_json::to_vec(self)?))
+    }
+
+    pub fn decode(token: &str) -> Result<Self> {
+        Ok(serde_json::from_slice(&base64::decode(token)?)?)
+    }
+}
+
+/// Verifies a `--credential` token (if one was passed) authorizes `operation` on
+/// `data_source_id`, reading the signing root key from `DUST_CAPABILITY_ROOT_KEY`. Deployments
+/// that don't set that env var are implicitly trusting all callers, matching the prior
+/// all-or-nothing behavior — this only tightens access once a root key and tokens are issued.
+fn check_credential(
+    credential: &Option<String>,
+    data_source_id: &str,
+    operation: CapabilityOperation,
+) -> Result<()> {
+    let root_key = match std::env::var("DUST_CAPABILITY_ROOT_KEY") {
+        Ok(key) => key,
+        Err(_) => return Ok(()),
+    };
+
+    let token = match credential {
+        Some(token) => CapabilityToken::decode(token)?,
+        None => Err(anyhow!(
+            "Missing `--credential`: this deployment requires a capability token"
+        ))?,
+    };
+
+    token.verify(root_key.as_bytes(), data_source_id, operation, utils::now())
+}
+
+pub async fn cmd_register(data_source_id: &str, config: &DataSourceConfig) -> Result<()> {
+    let root_path = utils::init_check().await?;
+    let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
+    store.init().await?;
+    let project = Project::new_from_id(1);
+
+    let ds = DataSource::new(&project, data_source_id, config);
+
+    ds.setup(Credentials::new()).await?;
+    store.register_data_source(&project, &ds).await?;
+
+    utils::done(&format!("Registered data_source `{}`", ds.data_source_id(),));
+
+    Ok(())
+}
+
+/// Parses repeatable `key=value` strings (as accepted by `cmd_upsert --meta` and
+/// `cmd_list --where`) into a metadata map, rejecting entries missing the `=` separator.
+fn parse_metadata_pairs(pairs: &Vec<String>) -> Result<HashMap<String, String>> {
+    pairs
+        .iter()
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => Ok((key.to_string(), value.to_string())),
+            None => Err(anyhow!(
+                "Invalid `--meta`/`--where` entry `{}`, expected `key=value`",
+                pair
+            )),
+        })
+        .collect()
+}
+
+pub async fn cmd_upsert(
+    data_source_id: &str,
+    document_id: &str,
+    timestamp: Option<u64>,
+    tags: &Vec<String>,
+    source_url: &Option<String>,
+    text_path: &str,
+    location: &Option<GeoPoint>,
+    // Repeatable `--meta key=value` pairs, e.g. `author`, `lang`, `doc_type`: structured, exact-match
+    // fields complementing freeform `tags`, indexed for `cmd_list --where key=value` lookups.
+    meta: &Vec<String>,
+    r#async: bool,
+    credential: &Option<String>,
+) -> Result<()> {
+    check_credential(credential, data_source_id, CapabilityOperation::Upsert)?;
+
+    let metadata = parse_metadata_pairs(meta)?;
+
+    let root_path = utils::init_check().await?;
+    let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
+    store.init().await?;
+    let project = Project::new_from_id(1);
+
+    let ds = match store.load_data_source(&project, data_source_id).await? {
+        Some(ds) => ds,
+        None => Err(anyhow!("Data source `{}` not found", data_source_id))?,
+    };
+
+    // `--async` persists the upsert as a queued `Task` and returns immediately instead of blocking
+    // on chunking + embedding, which is painful for large files and batch loads; a separate
+    // `cmd_worker` process drains the queue and `cmd_task_status` polls progress.
+    if r#async {
+        let task = Task::new(
+            data_source_id,
+            document_id,
+            TaskPayload::Upsert {
+                timestamp,
+                tags: tags.clone(),
+                source_url: source_url.clone(),
+                text_path: text_path.to_string(),
+                location: location.clone(),
+                metadata,
+            },
+        );
+        store.enqueue_task(&task).await?;
+
+        utils::done(&format!(
+            "Enqueued upsert task: task_id={} data_source={} document_id={}",
+            task.task_id,
+            ds.data_source_id(),
+            document_id,
+        ));
+
+        return Ok(());
+    }
+
+    let text_path = &shellexpand::tilde(text_path).into_owned();
+    let text_path = std::path::Path::new(text_path);
+
+    let contents = async_fs::read(text_path).await?;
+    let text = std::str::from_utf8(&contents)?;
+
+    let d = ds
+        .upsert(
+            Credentials::new(),
+            Box::new(store.clone()),
+            document_id,
+            timestamp,
+            tags,
+            source_url,
+            text,
+            true, // preserve system tags
+            location,
+            &metadata,
+        )
+        .await?;
+
+    utils::done(&format!(
+        "Upserted document: data_source={} document_id={} text_length={} chunk_count={} tags={}",
+        ds.data_source_id(),
+        document_id,
+        text.len(),
+        d.chunks.len(),
+        tags.join(","),
+    ));
+
+    Ok(())
+}
+
+pub async fn cmd_search(
+    data_source_id: &str,
+    query: &str,
+    top_k: usize,
+    filter: &Option<String>,
+    rank_by: &Option<String>,
+    credential: &Option<String>,
+) -> Result<()> {
+    check_credential(credential, data_source_id, CapabilityOperation::Search)?;
+
+    let root_path = utils::init_check().await?;
+    let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
+    store.init().await?;
+    let project = Project::new_from_id(1);
+
+    let ds = match store.load_data_source(&project, data_source_id).await? {
+        Some(ds) => ds,
+        None => Err(anyhow!("Data source `{}` not found", data_source_id))?,
+    };
+
+    // `--filter` accepts the small `tag`/`timestamp` boolean grammar parsed by
+    // `FilterExpr::parse_query`, e.g. `tag in [customer-a] AND NOT timestamp < 1700000000`, so a
+    // query can be scoped to a subset of documents instead of always hitting the whole corpus.
+    let filter = filter.as_deref().map(FilterExpr::parse_query).transpose()?;
+
+    // `--rank-by similarity,exactness,recency` re-ranks candidates lexicographically by this
+    // ordered criteria list instead of leaving them in embedding-score order.
+    let rank_by = rank_by.as_deref().map(RankCriterion::parse_list).transpose()?;
+
+    let r = ds
+        .search(
+            Credentials::new(),
+            Box::new(store.clone()),
+            query,
+            top_k,
+            filter,
+            false,
+            None,
+            rank_by,
+        )
+        .await?;
+
+    utils::info(&format!(
+        "{} documents, {} chunks total",
+        r.len(),
+        r.iter().map(|d| d.chunks.len()).sum::<usize>(),
+    ));
+    r.iter().for_each(|d| {
+        utils::info(&format!(
+            "- Document: document_id={} text_size={} chunk_count={}",
+            d.document_id, d.text_size, d.chunk_count,
+        ));
+        d.chunks.iter().for_each(|c| {
+            utils::info(&format!(
+                "  > Chunk: offset={} score={}",
+                c.offset,
+                c.score.unwrap_or(0.0),
+            ));
+            println!("```\n{}\n```", c.text);
+        });
+    });
+
+    Ok(())
+}
+
+pub async fn cmd_retrieve(
+    data_source_id: &str,
+    document_id: &str,
+    credential: &Option<String>,
+) -> Result<()> {
+    check_credential(credential, data_source_id, CapabilityOperation::Search)?;
+
+    let root_path = utils::init_check().await?;
+    let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
+    store.init().await?;
+    let project = Project::new_from_id(1);
+
+    let ds = match store.load_data_source(&project, data_source_id).await? {
+        Some(ds) => ds,
+        None => Err(anyhow!("Data source `{}` not found", data_source_id))?,
+    };
+
+    let d = match ds
+        .retrieve(Box::new(store.clone()), document_id, true, &None)
+        .await?
+    {
+        Some(d) => d,
+        None => Err(anyhow!("Document not found: document_id={}", document_id))?,
+    };
+
+    utils::done(&format!(
+        "Retrieved document: data_source={} document_id={}",
+        ds.data_source_id(),
+        document_id,
+    ));
+
+    utils::info(&format!(
+        "- Document: document_id={} text_size={} chunk_count={}",
+        d.document_id, d.text_size, d.chunk_count,
+    ));
+
+    if !d.metadata.is_empty() {
+        let mut metadata = d.metadata.iter().collect::<Vec<_>>();
+        metadata.sort_by(|a, b| a.0.cmp(b.0));
+        utils::info(&format!(
+            "- Metadata: {}",
+            metadata
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ));
+    }
+
+    match d.text {
+        Some(text) => {
+            println!("```\n{}\n```", text);
+        }
+        None => (),
+    }
+
+    Ok(())
+}
+
+pub async fn cmd_delete(
+    data_source_id: &str,
+    document_id: &str,
+    credential: &Option<String>,
+) -> Result<()> {
+    check_credential(credential, data_source_id, CapabilityOperation::Delete)?;
+
+    let root_path = utils::init_check().await?;
+    let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
+    store.init().await?;
+    let project = Project::new_from_id(1);
+
+    let ds = match store.load_data_source(&project, data_source_id).await? {
+        Some(ds) => ds,
+        None => Err(anyhow!("Data source `{}` not found", data_source_id))?,
+    };
+
+    ds.delete_document(Box::new(store.clone()), document_id)
+        .await?;
+
+    utils::done(&format!(
+        "Deleted document: data_source={} document_id={}",
+        ds.data_source_id(),
+        document_id,
+    ));
+
+    Ok(())
+}
+
+pub async fn cmd_list(
+    data_source_id: &str,
+    // `--where key=value` narrows the listing to documents whose metadata contains this exact
+    // pair, joining against the `(data_source_id, key, value) -> document_id` index populated by
+    // `cmd_upsert --meta` instead of scanning every document's metadata.
+    r#where: &Option<String>,
+    credential: &Option<String>,
+) -> Result<()> {
+    check_credential(credential, data_source_id, CapabilityOperation::Search)?;
+
+    let root_path = utils::init_check().await?;
+    let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
+    store.init().await?;
+    let project = Project::new_from_id(1);
+
+    let documents = match r#where {
+        Some(clause) => {
+            let metadata = parse_metadata_pairs(&vec![clause.clone()])?;
+            let (key, value) = metadata
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("`--where` requires a single `key=value` pair"))?;
+
+            store
+                .list_data_source_documents_by_metadata(&project, data_source_id, &key, &value)
+                .await?
+        }
+        None => {
+            store
+                .list_data_source_documents(
+                    &project,
+                    data_source_id,
+                    None,
+                    true, // remove system tags
+                )
+                .await?
+                .0
+        }
+    };
+
+    utils::info(&format!("{} documents", documents.len(),));
+    documents.iter().for_each(|d| {
+        utils::info(&format!(
+            "- Document: document_id={} text_size={} chunk_count={}",
+            d.document_id, d.text_size, d.chunk_count,
+        ));
+    });
+
+    Ok(())
+}
+
+pub async fn cmd_task_status(task_id: &str) -> Result<()> {
+    let root_path = utils::init_check().await?;
+    let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
+    store.init().await?;
+
+    let task = match store.load_task(task_id).await? {
+        Some(task) => task,
+        None => Err(anyhow!("Task `{}` not found", task_id))?,
+    };
+
+    println!("{}", serde_json::to_string(&task)?);
+
+    Ok(())
+}
+
+/// Lists queued tasks, optionally narrowed to one data source and/or one `TaskStatus`, so callers
+/// can poll progress across a batch load or find failures to retry.
+pub async fn cmd_task_list(
+    data_source_id: &Option<String>,
+    status: &Option<String>,
+) -> Result<()> {
+    let root_path = utils::init_check().await?;
+    let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
+    store.init().await?;
 
-        utils::done(&format!(
-            "Deleted Data Source records: data_source_id={}",
-            self.data_source_id,
+    let status = status.as_deref().map(TaskStatus::parse).transpose()?;
+    let tasks = store
+        .list_tasks(data_source_id.as_deref(), status.clone())
+        .await?;
+
+    utils::info(&format!("{} tasks", tasks.len()));
+    tasks.iter().for_each(|t| {
+        utils::info(&format!(
+            "- Task: task_id={} data_source_id={} document_id={} status={:?} updated_at={}",
+            t.task_id, t.data_source_id, t.document_id, t.status, t.updated_at,
         ));
+    });
 
-        Ok(())
-    }
+    Ok(())
 }
 
-pub async fn cmd_register(data_source_id: &str, config: &DataSourceConfig) -> Result<()> {
+/// Drains the task queue, running each `enqueued` task's `TaskPayload` and recording the outcome.
+/// Intended to run as a long-lived background process alongside the write API so `cmd_upsert
+/// --async` can return as soon as the task is persisted; polls at `poll_interval` when the queue
+/// is empty rather than busy-looping.
+pub async fn cmd_worker(poll_interval: std::time::Duration) -> Result<()> {
     let root_path = utils::init_check().await?;
     let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
     store.init().await?;
     let project = Project::new_from_id(1);
 
-    let ds = DataSource::new(&project, data_source_id, config);
+    utils::done(&format!("Starting task worker: poll_interval={:?}", poll_interval));
 
-    ds.setup(Credentials::new()).await?;
-    store.register_data_source(&project, &ds).await?;
+    loop {
+        let task = match store.dequeue_task().await? {
+            Some(task) => task,
+            None => {
+                tokio::time::sleep(poll_interval).await;
+                continue;
+            }
+        };
 
-    utils::done(&format!("Registered data_source `{}`", ds.data_source_id(),));
+        utils::info(&format!(
+            "Processing task: task_id={} data_source_id={} document_id={}",
+            task.task_id, task.data_source_id, task.document_id,
+        ));
 
-    Ok(())
+        match run_task(&store, &project, &task).await {
+            Ok(()) => {
+                store
+                    .update_task_status(&task.task_id, TaskStatus::Succeeded, None)
+                    .await?;
+                utils::done(&format!("Task succeeded: task_id={}", task.task_id));
+            }
+            Err(e) => {
+                store
+                    .update_task_status(&task.task_id, TaskStatus::Failed, Some(e.to_string()))
+                    .await?;
+                utils::error(&format!("Task failed: task_id={} error={}", task.task_id, e));
+            }
+        }
+    }
 }
 
-pub async fn cmd_upsert(
-    data_source_id: &str,
-    document_id: &str,
+/// One JSONL-file entry accepted by `cmd_import`: `{document_id, text, tags?, source_url?,
+/// timestamp?}`, the same shape `cmd_upsert` takes as separate arguments.
+#[derive(Debug, Deserialize)]
+struct ImportEntry {
+    document_id: String,
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    source_url: Option<String>,
     timestamp: Option<u64>,
-    tags: &Vec<String>,
-    source_url: &Option<String>,
-    text_path: &str,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+/// Running/final counts reported by `cmd_import`, so a large batch import doesn't have to be
+/// babysat line-by-line to know how it's going or how it went.
+#[derive(Debug, Serialize, Default)]
+pub struct ImportReport {
+    pub documents_upserted: usize,
+    pub documents_failed: usize,
+    pub chunk_count: usize,
+    pub text_bytes: u64,
+}
+
+/// Ingests many documents into a data source in one pass: a JSONL file (one `ImportEntry` per
+/// line) or a `.tar`/`.tar.gz` archive (each entry becomes a document keyed by its archive path).
+/// Entries are streamed and upserted one at a time rather than loaded into memory all at once, so
+/// this scales to corpora that don't fit in RAM; combined with `cmd_upsert --async` this makes
+/// seeding a data source from an existing corpus practical.
+pub async fn cmd_import(
+    data_source_id: &str,
+    path: &str,
+    credential: &Option<String>,
 ) -> Result<()> {
+    check_credential(credential, data_source_id, CapabilityOperation::Upsert)?;
+
     let root_path = utils::init_check().await?;
     let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
     store.init().await?;
@@ -1256,13 +3829,118 @@ pub async fn cmd_upsert(
         None => Err(anyhow!("Data source `{}` not found", data_source_id))?,
     };
 
-    let text_path = &shellexpand::tilde(text_path).into_owned();
-    let text_path = std::path::Path::new(text_path);
+    let path = shellexpand::tilde(path).into_owned();
+    let path = std::path::Path::new(&path);
 
-    let contents = async_fs::read(text_path).await?;
-    let text = std::str::from_utf8(&contents)?;
+    let mut report = ImportReport::default();
 
-    let d = ds
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("tar") => {
+            let archive = tar::Archive::new(std::fs::File::open(path)?);
+            import_tar(&ds, &store, archive, &mut report).await?;
+        }
+        Some("gz") | Some("tgz") => {
+            let decoder = flate2::read::GzDecoder::new(std::fs::File::open(path)?);
+            import_tar(&ds, &store, tar::Archive::new(decoder), &mut report).await?;
+        }
+        _ => import_jsonl(&ds, &store, path, &mut report).await?,
+    }
+
+    utils::done(&format!(
+        "Imported data source: data_source_id={} documents_upserted={} documents_failed={} \
+         chunk_count={} text_bytes={}",
+        data_source_id,
+        report.documents_upserted,
+        report.documents_failed,
+        report.chunk_count,
+        report.text_bytes,
+    ));
+    println!("{}", serde_json::to_string(&report)?);
+
+    Ok(())
+}
+
+async fn import_jsonl(
+    ds: &DataSource,
+    store: &SQLiteStore,
+    path: &std::path::Path,
+    report: &mut ImportReport,
+) -> Result<()> {
+    let file = std::fs::File::open(path)?;
+    for line in std::io::BufRead::lines(std::io::BufReader::new(file)) {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: ImportEntry = match serde_json::from_str(&line) {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.documents_failed += 1;
+                utils::error(&format!("Import entry parse error: {}", e));
+                continue;
+            }
+        };
+        import_upsert(
+            ds,
+            store,
+            &entry.document_id,
+            entry.timestamp,
+            &entry.tags,
+            &entry.source_url,
+            &entry.text,
+            &entry.metadata,
+            report,
+        )
+        .await;
+    }
+    Ok(())
+}
+
+async fn import_tar<R: std::io::Read>(
+    ds: &DataSource,
+    store: &SQLiteStore,
+    mut archive: tar::Archive<R>,
+    report: &mut ImportReport,
+) -> Result<()> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+        let document_id = entry.path()?.to_string_lossy().into_owned();
+        let mut text = String::new();
+        std::io::Read::read_to_string(&mut entry, &mut text)?;
+
+        import_upsert(
+            ds,
+            store,
+            &document_id,
+            None,
+            &vec![],
+            &None,
+            &text,
+            &HashMap::new(),
+            report,
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// Upserts one `cmd_import` entry, folding the outcome into `report` and logging failures instead
+/// of aborting the whole import so one bad document doesn't lose the rest of the batch.
+async fn import_upsert(
+    ds: &DataSource,
+    store: &SQLiteStore,
+    document_id: &str,
+    timestamp: Option<u64>,
+    tags: &Vec<String>,
+    source_url: &Option<String>,
+    text: &str,
+    metadata: &HashMap<String, String>,
+    report: &mut ImportReport,
+) {
+    let result = ds
         .upsert(
             Credentials::new(),
             Box::new(store.clone()),
@@ -1272,22 +3950,67 @@ pub async fn cmd_upsert(
             source_url,
             text,
             true, // preserve system tags
+            &None,
+            metadata,
         )
-        .await?;
+        .await;
 
-    utils::done(&format!(
-        "Upserted document: data_source={} document_id={} text_length={} chunk_count={} tags={}",
-        ds.data_source_id(),
-        document_id,
-        text.len(),
-        d.chunks.len(),
-        tags.join(","),
-    ));
+    match result {
+        Ok(d) => {
+            report.documents_upserted += 1;
+            report.chunk_count += d.chunks.len();
+            report.text_bytes += text.len() as u64;
+        }
+        Err(e) => {
+            report.documents_failed += 1;
+            utils::error(&format!(
+                "Import upsert failed: document_id={} error={}",
+                document_id, e
+            ));
+        }
+    }
 
-    Ok(())
+    let processed = report.documents_upserted + report.documents_failed;
+    if processed % 100 == 0 {
+        utils::info(&format!(
+            "Import progress: upserted={} failed={}",
+            report.documents_upserted, report.documents_failed,
+        ));
+    }
+}
+
+/// Machine-readable report emitted by `cmd_analyze`: per-data-source storage accounting plus an
+/// estimate of space reclaimable by `--vacuum`.
+#[derive(Debug, Serialize)]
+pub struct StorageReport {
+    pub data_source_id: String,
+    pub document_count: usize,
+    pub chunk_count: usize,
+    pub text_bytes: u64,
+    pub vector_bytes: u64,
+    pub orphaned_chunk_count: usize,
+    pub deleted_document_count: usize,
+    pub reclaimable_bytes: u64,
 }
 
-pub async fn cmd_search(data_source_id: &str, query: &str, top_k: usize) -> Result<()> {
+/// Walks a data source's Qdrant collection and SQL records to report storage usage and estimate
+/// reclaimable space: chunk/embedding points whose `document_id` no longer has a live document
+/// (orphaned by a delete that didn't clean up its points), plus soft-deleted document tombstones
+/// still holding their on-disk text. With `vacuum`, actually deletes the orphaned points and
+/// tombstones and runs SQLite `VACUUM`, giving operators the equivalent of a bundle-usage
+/// analysis to decide when to compact.
+///
+/// The read-only report needs no credential, but `--vacuum` deletes Qdrant points and runs SQLite
+/// `VACUUM`, so it's gated behind `CapabilityOperation::Delete` like `cmd_delete`.
+pub async fn cmd_analyze(
+    data_source_id: &str,
+    vacuum: bool,
+    credential: &Option<String>,
+) -> Result<()> {
+    if vacuum {
+        check_credential(credential, data_source_id, CapabilityOperation::Delete)?;
+    }
+
     let root_path = utils::init_check().await?;
     let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
     store.init().await?;
@@ -1298,42 +4021,135 @@ pub async fn cmd_search(data_source_id: &str, query: &str, top_k: usize) -> Resu
         None => Err(anyhow!("Data source `{}` not found", data_source_id))?,
     };
 
-    let r = ds
-        .search(
-            Credentials::new(),
-            Box::new(store.clone()),
-            query,
-            top_k,
-            None,
-            false,
-            None,
-        )
+    let mut embedder = provider(ds.config().provider_id).embedder(ds.config().model_id.clone());
+    embedder.initialize(Credentials::new()).await?;
+    let vector_bytes_per_chunk = (embedder.embedding_size() * std::mem::size_of::<f32>()) as u64;
+
+    let (live_documents, _) = store
+        .list_data_source_documents(&project, data_source_id, None, false)
         .await?;
+    let live_document_ids = live_documents
+        .iter()
+        .map(|d| d.document_id.clone())
+        .collect::<std::collections::HashSet<_>>();
 
-    utils::info(&format!(
-        "{} documents, {} chunks total",
-        r.len(),
-        r.iter().map(|d| d.chunks.len()).sum::<usize>(),
-    ));
-    r.iter().for_each(|d| {
-        utils::info(&format!(
-            "- Document: document_id={} text_size={} chunk_count={}",
-            d.document_id, d.text_size, d.chunk_count,
+    let qdrant_client = ds.qdrant_client().await?;
+    let collection = ds.qdrant_collection();
+
+    let mut chunk_count = 0usize;
+    let mut text_bytes = 0u64;
+    let mut orphaned_chunk_count = 0usize;
+    let mut orphaned_text_bytes = 0u64;
+    let mut orphaned_document_ids = std::collections::HashSet::new();
+
+    let mut offset = None;
+    loop {
+        let resp = qdrant_client
+            .scroll(&qdrant::ScrollPoints {
+                collection_name: collection.clone(),
+                filter: None,
+                limit: Some(256),
+                offset,
+                with_payload: Some(true.into()),
+                with_vectors: Some(false.into()),
+                ..Default::default()
+            })
+            .await?;
+
+        for point in resp.result.iter() {
+            let (document_id, chunk) = parse_chunk_result(&point.payload, None)?;
+            chunk_count += 1;
+            text_bytes += chunk.text.len() as u64;
+            if !live_document_ids.contains(&document_id) {
+                orphaned_chunk_count += 1;
+                orphaned_text_bytes += chunk.text.len() as u64;
+                orphaned_document_ids.insert(document_id);
+            }
+        }
+
+        offset = resp.next_page_offset;
+        if offset.is_none() {
+            break;
+        }
+    }
+
+    let deleted_documents = store
+        .list_deleted_data_source_documents(&project, data_source_id)
+        .await?;
+    let deleted_text_bytes = deleted_documents.iter().map(|d| d.text_size).sum::<u64>();
+
+    let vector_bytes = chunk_count as u64 * vector_bytes_per_chunk;
+    let orphaned_vector_bytes = orphaned_chunk_count as u64 * vector_bytes_per_chunk;
+    let reclaimable_bytes = orphaned_text_bytes + orphaned_vector_bytes + deleted_text_bytes;
+
+    let report = StorageReport {
+        data_source_id: data_source_id.to_string(),
+        document_count: live_documents.len(),
+        chunk_count,
+        text_bytes,
+        vector_bytes,
+        orphaned_chunk_count,
+        deleted_document_count: deleted_documents.len(),
+        reclaimable_bytes,
+    };
+
+    if vacuum {
+        if !orphaned_document_ids.is_empty() {
+            // An orphaned document's points may include a dedup canonical still backing a live
+            // document's chunk (non-empty `chunk_refs`): promote it away first, the same as the
+            // upsert/delete path does, so deleting by `document_id` below can't destroy a live
+            // document's only copy of a shared chunk.
+            for document_id in orphaned_document_ids.iter() {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(document_id.as_bytes());
+                let document_id_hash = format!("{}", hasher.finalize().to_hex());
+                ds.promote_canonical_points(&qdrant_client, &document_id_hash)
+                    .await?;
+            }
+            qdrant_client
+                .delete_points(
+                    collection.clone(),
+                    &qdrant::Filter {
+                        must: vec![qdrant::FieldCondition {
+                            key: "document_id".to_string(),
+                            r#match: Some(qdrant::Match {
+                                match_value: Some(qdrant::r#match::MatchValue::Keywords(
+                                    qdrant::RepeatedStrings {
+                                        strings: orphaned_document_ids.into_iter().collect(),
+                                    },
+                                )),
+                            }),
+                            ..Default::default()
+                        }
+                        .into()],
+                        ..Default::default()
+                    }
+                    .into(),
+                    None,
+                )
+                .await?;
+        }
+        store
+            .purge_deleted_data_source_documents(&project, data_source_id)
+            .await?;
+        store.vacuum().await?;
+
+        utils::done(&format!(
+            "Vacuumed data source: data_source_id={} reclaimed_bytes={}",
+            data_source_id, report.reclaimable_bytes,
         ));
-        d.chunks.iter().for_each(|c| {
-            utils::info(&format!(
-                "  > Chunk: offset={} score={}",
-                c.offset,
-                c.score.unwrap_or(0.0),
-            ));
-            println!("```\n{}\n```", c.text);
-        });
-    });
+    }
+
+    println!("{}", serde_json::to_string(&report)?);
 
     Ok(())
 }
 
-pub async fn cmd_retrieve(data_source_id: &str, document_id: &str) -> Result<()> {
+/// Replays a JSON array of `BenchRequest` workloads against a registered data source, collecting
+/// per-stage `SearchTiming` from `search_instrumented` for each one. The expansion scroll (the
+/// `target_document_tokens_offsets` path) is the likeliest hotspot, so it gets its own percentile
+/// breakdown rather than being folded into the end-to-end latency.
+pub async fn cmd_bench(data_source_id: &str, workload_path: &str) -> Result<()> {
     let root_path = utils::init_check().await?;
     let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
     store.init().await?;
@@ -1344,87 +4160,225 @@ pub async fn cmd_retrieve(data_source_id: &str, document_id: &str) -> Result<()>
         None => Err(anyhow!("Data source `{}` not found", data_source_id))?,
     };
 
-    let d = match ds
-        .retrieve(Box::new(store.clone()), document_id, true, &None)
-        .await?
-    {
-        Some(d) => d,
-        None => Err(anyhow!("Document not found: document_id={}", document_id))?,
+    let workload_path = &shellexpand::tilde(workload_path).into_owned();
+    let contents = async_fs::read_to_string(workload_path).await?;
+    let requests: Vec<BenchRequest> = serde_json::from_str(&contents)?;
+
+    let mut total_ms = vec![];
+    let mut qdrant_ms = vec![];
+    let mut keyword_ms = vec![];
+    let mut blob_fetch_ms = vec![];
+    let mut expansion_ms = vec![];
+    let mut error_count = 0;
+
+    for r in requests.iter() {
+        let mut timing = SearchTiming::default();
+        match ds
+            .search_instrumented(
+                Credentials::new(),
+                Box::new(store.clone()),
+                &r.query,
+                r.top_k,
+                r.filter.clone(),
+                r.full_text,
+                r.target_document_tokens,
+                None,
+                Some(&mut timing),
+            )
+            .await
+        {
+            Ok(_) => {
+                total_ms.push(timing.total_ms);
+                qdrant_ms.push(timing.qdrant_ms);
+                if r.full_text {
+                    keyword_ms.push(timing.keyword_ms);
+                }
+                blob_fetch_ms.push(timing.blob_fetch_ms);
+                expansion_ms.push(timing.expansion_ms);
+            }
+            Err(e) => {
+                error_count += 1;
+                utils::error(&format!(
+                    "Bench request failed: query={} error={}",
+                    r.query, e
+                ));
+            }
+        }
+    }
+
+    let report = BenchReport {
+        request_count: requests.len(),
+        error_count,
+        total_ms: BenchPercentiles::from_samples(total_ms),
+        qdrant_ms: BenchPercentiles::from_samples(qdrant_ms),
+        keyword_ms: BenchPercentiles::from_samples(keyword_ms),
+        blob_fetch_ms: BenchPercentiles::from_samples(blob_fetch_ms),
+        expansion_ms: BenchPercentiles::from_samples(expansion_ms),
     };
 
     utils::done(&format!(
-        "Retrieved document: data_source={} document_id={}",
-        ds.data_source_id(),
-        document_id,
+        "Benchmarked data source: data_source_id={} request_count={} errors={}",
+        data_source_id, report.request_count, report.error_count,
     ));
+    println!("{}", serde_json::to_string(&report)?);
 
-    utils::info(&format!(
-        "- Document: document_id={} text_size={} chunk_count={}",
-        d.document_id, d.text_size, d.chunk_count,
-    ));
+    Ok(())
+}
 
-    match d.text {
-        Some(text) => {
-            println!("```\n{}\n```", text);
-        }
-        None => (),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_token_verify() {
+        let root_key = b"test-root-key";
+        let token = CapabilityToken::mint("tok_1", root_key)
+            .with_caveat(Caveat::DataSource("ds_1".to_string()))
+            .with_caveat(Caveat::Operation(vec![CapabilityOperation::Search]));
+
+        // Valid for the scoped data source and operation.
+        assert!(token
+            .verify(root_key, "ds_1", CapabilityOperation::Search, 0)
+            .is_ok());
+
+        // Wrong data source, wrong operation, and wrong root key are all rejected.
+        assert!(token
+            .verify(root_key, "ds_2", CapabilityOperation::Search, 0)
+            .is_err());
+        assert!(token
+            .verify(root_key, "ds_1", CapabilityOperation::Upsert, 0)
+            .is_err());
+        assert!(token
+            .verify(b"wrong-key", "ds_1", CapabilityOperation::Search, 0)
+            .is_err());
+
+        // A tampered signature (e.g. bit-flipped hex) fails too.
+        let mut tampered = token.clone();
+        tampered.signature = format!("{:0>width$}", 0, width = tampered.signature.len());
+        assert!(tampered
+            .verify(root_key, "ds_1", CapabilityOperation::Search, 0)
+            .is_err());
+
+        // Dropping a caveat without the root key (here the operation scope, to widen it to
+        // `Upsert`) invalidates the chain.
+        let mut forged = token.clone();
+        forged.caveats.pop();
+        assert!(forged
+            .verify(root_key, "ds_1", CapabilityOperation::Upsert, 0)
+            .is_err());
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_capability_token_expiry() {
+        let root_key = b"test-root-key";
+        let token =
+            CapabilityToken::mint("tok_2", root_key).with_caveat(Caveat::ExpiresAt(100));
+
+        assert!(token
+            .verify(root_key, "ds_1", CapabilityOperation::Search, 50)
+            .is_ok());
+        assert!(token
+            .verify(root_key, "ds_1", CapabilityOperation::Search, 101)
+            .is_err());
+    }
 
-pub async fn cmd_delete(data_source_id: &str, document_id: &str) -> Result<()> {
-    let root_path = utils::init_check().await?;
-    let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
-    store.init().await?;
-    let project = Project::new_from_id(1);
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let dense = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let keyword = vec!["b".to_string(), "c".to_string(), "a".to_string()];
+        let scores = reciprocal_rank_fusion(&[dense, keyword], 60.0);
+
+        // "b" is 2nd in dense and 1st in keyword, "a" is 1st in dense and 3rd in keyword: both
+        // have a 1st place, so "b"'s better other rank (2nd vs 3rd) should edge it out.
+        assert!(scores["b"] > scores["a"]);
+        assert!(scores["a"] > scores["c"]);
+
+        let expected_a = 1.0 / (60.0 + 1.0) + 1.0 / (60.0 + 3.0);
+        assert!((scores["a"] - expected_a).abs() < f64::EPSILON);
+    }
 
-    let ds = match store.load_data_source(&project, data_source_id).await? {
-        Some(ds) => ds,
-        None => Err(anyhow!("Data source `{}` not found", data_source_id))?,
-    };
+    fn test_chunk(hash: &str, score: f64) -> Chunk {
+        Chunk {
+            text: "text".to_string(),
+            hash: hash.to_string(),
+            offset: 0,
+            vector: None,
+            score: Some(score),
+            score_details: None,
+            content_hash: hash.to_string(),
+            dedup_refs: vec![],
+        }
+    }
 
-    ds.delete_document(Box::new(store.clone()), document_id)
-        .await?;
+    #[test]
+    fn test_combine_weighted_exposes_norms() {
+        let dense_chunks = vec![
+            ("doc_a".to_string(), test_chunk("h1", 0.0)),
+            ("doc_b".to_string(), test_chunk("h2", 1.0)),
+        ];
+        let keyword_chunks = vec![
+            ("doc_a".to_string(), test_chunk("h1", 2.0)),
+            ("doc_b".to_string(), test_chunk("h2", 4.0)),
+        ];
+        let mut score_details_by_hash: HashMap<String, ChunkScoreDetails> = HashMap::new();
+
+        let combined = combine_weighted(
+            dense_chunks,
+            keyword_chunks,
+            0.5,
+            10,
+            &mut score_details_by_hash,
+        );
 
-    utils::done(&format!(
-        "Deleted document: data_source={} document_id={}",
-        ds.data_source_id(),
-        document_id,
-    ));
+        assert_eq!(combined.len(), 2);
+        let h1_details = score_details_by_hash.get("h1").unwrap();
+        assert_eq!(h1_details.semantic_norm, Some(0.0));
+        assert_eq!(h1_details.keyword_norm, Some(0.0));
+        let h2_details = score_details_by_hash.get("h2").unwrap();
+        assert_eq!(h2_details.semantic_norm, Some(1.0));
+        assert_eq!(h2_details.keyword_norm, Some(1.0));
+    }
 
-    Ok(())
-}
+    #[test]
+    fn test_proximity_span() {
+        let terms = vec!["fox".to_string(), "dog".to_string()];
 
-pub async fn cmd_list(data_source_id: &str) -> Result<()> {
-    let root_path = utils::init_check().await?;
-    let store = SQLiteStore::new(root_path.join("store.sqlite"))?;
-    store.init().await?;
-    let project = Project::new_from_id(1);
+        // "fox" is at token index 3, "dog" at index 8: the only window spanning both is 6 tokens.
+        let span = proximity_span("the quick brown fox jumps over the lazy dog", &terms);
+        assert_eq!(span, 6);
 
-    let r = store
-        .list_data_source_documents(
-            &project,
-            data_source_id,
-            None,
-            true, // remove system tags
-        )
-        .await?;
+        // No match at all: MAX.
+        assert_eq!(
+            proximity_span("completely unrelated text", &terms),
+            usize::MAX
+        );
 
-    utils::info(&format!("{} documents", r.0.len(),));
-    r.0.iter().for_each(|d| {
-        utils::info(&format!(
-            "- Document: document_id={} text_size={} chunk_count={}",
-            d.document_id, d.text_size, d.chunk_count,
-        ));
-    });
+        // Empty query terms: MAX (nothing to anchor a window on).
+        assert_eq!(proximity_span("the quick brown fox", &[]), usize::MAX);
+    }
 
-    Ok(())
-}
+    #[test]
+    fn test_rank_key_cmp_similarity_then_recency() {
+        let mut high_sim = test_chunk("h1", 0.9);
+        high_sim.text = "alpha".to_string();
+        let mut low_sim = test_chunk("h2", 0.1);
+        low_sim.text = "beta".to_string();
+
+        let a = rank_key(&high_sim, 100, &[]);
+        let b = rank_key(&low_sim, 200, &[]);
+
+        // Similarity is the primary key: higher similarity sorts first regardless of recency.
+        assert_eq!(
+            rank_key_cmp(&a, &b, &[RankCriterion::Similarity, RankCriterion::Recency]),
+            std::cmp::Ordering::Less
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // With only Recency as the criterion, the more recent document (b) sorts first.
+        assert_eq!(
+            rank_key_cmp(&a, &b, &[RankCriterion::Recency]),
+            std::cmp::Ordering::Greater
+        );
+    }
 
     #[test]
     fn test_c() {